@@ -1,10 +1,36 @@
-use std::vec;
-
 use eframe::emath;
 use egui::{ Color32, Painter, Pos2, Rect, Stroke, StrokeKind};
-use egui_plot::{Line, Plot, PlotPoints};
+use egui_plot::{HLine, Line, Plot, PlotPoints, VLine};
+
+use crate::balance::{BalanceCheck, PhaseSpaceCheck};
+use crate::control::Control;
+use crate::diagnostics::Diagnostics;
+use crate::ensemble::{Background, Ensemble};
+use crate::field::ExternalPotential;
+use crate::files;
+use crate::history::History;
+use crate::observables::{Observable, Recorder};
+use crate::physics::{Energy, InitialCondition, Kernel, MaxwellType, Parameters, Simulation};
+use crate::potential::Interaction;
+use crate::ratchet::RatchetParameters;
+use crate::szilard::SzilardParameters;
+use crate::raster::{ApngEncoder, Image, RecordingOptions};
+use crate::scenario::{BUNDLED, Scenario};
+use crate::scene::{Colouring, Scene, Style, Trails};
+use crate::svg::{self, SvgOptions};
+
+/// Where the web build is published, the base of links copied from the native app
+const DEMO_URL: &str = "https://sitandr.github.io/Maxwell/";
+
+/// Samples of the detailed balance check
+const BALANCE_SAMPLES: usize = 200_000;
+/// Samples of the phase-space check, each mapped seven times
+const PHASE_SPACE_SAMPLES: usize = 20_000;
+/// Frames the checks are spread over
+const CHECK_FRAMES: usize = 20;
 
-use crate::physics::{Simulation, MaxwellType};
+/// Most frames a recording captures, two minutes at 60 frames a second
+const MAX_RECORDED_FRAMES: usize = 7200;
 
 /// We derive Deserialize/Serialize so we can persist app state on shutdown.
 #[derive(serde::Deserialize, serde::Serialize)]
@@ -15,47 +41,117 @@ pub struct TemplateApp {
     #[serde(skip)]
     paused: bool,
     #[serde(skip)]
-    points: Vec<(f64, f64)>,
+    recorder: Recorder,
+    #[serde(skip)]
+    step_once: bool,
+    #[serde(skip)]
+    history: History,
+    /// Index of the snapshot being inspected, `None` when showing the present
+    #[serde(skip)]
+    rewind: Option<usize>,
+    /// Outcome of the last export, shown under the plot
+    #[serde(skip)]
+    status: String,
+    /// Frames captured so far, at most [`MAX_RECORDED_FRAMES`], `None` when not recording
+    #[serde(skip)]
+    recorded: Option<Vec<Scene>>,
+    /// Simulated time the next frame of the recording is captured at
+    #[serde(skip)]
+    next_frame: f64,
+    /// Dark mode of the last painted frame, so the exports look like the screen
+    #[serde(skip)]
+    dark_mode: bool,
+    #[serde(skip)]
+    trails: Trails,
+    /// Scenario being edited in the Scenario window
+    #[serde(skip)]
+    scenario_text: String,
+    #[serde(skip)]
+    scenario_path: String,
     #[serde(skip)]
-    time: f64,
+    scenario_status: String,
+    /// Parameters the current run was started with, the edited ones only apply on restart
+    #[serde(skip)]
+    running: Parameters,
+    /// Address of the page shared links point to
+    #[serde(skip)]
+    page_url: String,
+    #[serde(skip)]
+    link_status: String,
+    /// Why the last run couldn't start, the one before it goes on meanwhile
+    #[serde(skip)]
+    initiation_status: String,
+    /// Replicas running alongside the shown simulation, `None` when not started
+    #[serde(skip)]
+    ensemble: Option<Background>,
+    #[serde(skip)]
+    diagnostics: Diagnostics,
+    /// Last detailed balance check, made a part every frame
+    #[serde(skip)]
+    balance: Option<BalanceCheck>,
+    /// Last phase-space check, with the demon it was made for
+    #[serde(skip)]
+    phase_space: Option<(MaxwellType, Result<PhaseSpaceCheck, String>)>,
 
 
-    temperature: f32,
-    balls_n: u16,
-    radius: f32,
-    filter_height: f32,
-    filter_temperature: f32,
+    parameters: Parameters,
+    randomize_seed: bool,
     filter_constant: f32,
-    filter_type: MaxwellType,
-    wall_width: f32,
-    collisions: bool,
 
-    measure_time: f64,
-    current_frames: u32,
-    current_sum: f64
+    dt: f32,
+    steps_per_frame: u32,
+    history_length: usize,
+    colouring: Colouring,
+    recording: RecordingOptions,
+    png_sequence: bool,
+    svg: SvgOptions,
+    replicas: usize,
+    ensemble_observable: Observable,
+
+    measure_time: f64
 
 }
 
 impl Default for TemplateApp {
     fn default() -> Self {
         Self {
-            collisions: true,
-            time: 0.0,
-            points: vec![],
-            temperature: 1.0,
-            balls_n: 60,
-            radius: 0.009,
-            filter_type: MaxwellType::Tennis,
-            filter_height: 0.8,
-            filter_temperature: 1.0,
+            recorder: Recorder::default(),
+            step_once: false,
+            history: History::default(),
+            rewind: None,
+            status: String::new(),
+            recorded: None,
+            next_frame: 0.0,
+            dark_mode: true,
+            trails: Trails::new(50),
+            scenario_text: String::new(),
+            scenario_path: String::new(),
+            scenario_status: String::new(),
+            running: Parameters::default(),
+            page_url: DEMO_URL.into(),
+            link_status: String::new(),
+            initiation_status: String::new(),
+            ensemble: None,
+            diagnostics: Diagnostics::new(&Simulation::new()),
+            balance: None,
+            phase_space: None,
+            parameters: Parameters::default(),
+            randomize_seed: true,
             simulation:  Simulation::new(),
-            wall_width: 0.05,
             filter_constant: 0.1,
             paused: false,
 
-            measure_time: 0.3,
-            current_frames: 0,
-            current_sum: 0.0
+            dt: 0.01,
+            steps_per_frame: 1,
+            history_length: 600,
+            colouring: Colouring::Plain,
+            recording: RecordingOptions::default(),
+            png_sequence: false,
+            svg: SvgOptions::default(),
+            replicas: 8,
+            ensemble_observable: Observable::Density,
+
+            measure_time: 0.3
         }
     }
 }
@@ -74,31 +170,244 @@ impl TemplateApp {
         else{
             Default::default()
         };
-        app.initiate();
+        app.history.set_capacity(app.history_length);
+
+        // a shared link overrides the stored settings
+        #[cfg(target_arch = "wasm32")]
+        {
+            let location = &cc.integration_info.web_info.location;
+            app.page_url = location.url.clone();
+            if let Some(scenario) = Scenario::from_fragment(&location.hash){
+                match scenario{
+                    Ok(scenario) => {
+                        app.apply_scenario(&scenario);
+                        app.randomize_seed = false;
+                        app.initiate();
+                        return app
+                    },
+                    Err(e) => {
+                        app.regenerate();
+                        app.link_status = e;
+                        return app
+                    }
+                }
+            }
+        }
+
+        app.regenerate();
         app
     }
 
     pub fn initiate(&mut self){
-        self.simulation.random_initiation(self.balls_n, self.temperature, self.radius, self.filter_height, self.filter_type, self.collisions, self.wall_width);
+        if let Err(e) = self.simulation.random_initiation(&self.parameters){
+            self.initiation_status = format!("Can't start: {e}");
+            return
+        }
+        self.initiation_status.clear();
+        self.running = self.parameters.clone();
+        self.link_status.clear();
+        self.recorder.window = self.measure_time;
+        self.recorder.clear(&self.simulation);
+        self.history.clear();
+        self.history.record(&self.simulation);
+        self.diagnostics.clear(&self.simulation);
+        self.trails.clear();
+        self.rewind = None;
+        if self.ensemble.is_some(){
+            self.start_ensemble();
+        }
     }
 
-    /// Set preset 1
-    fn set(n: u8) -> Self{
-        let mut s = Self::default();
-        match n{
-            1 => {},
-            2 => {
-                s.filter_type = MaxwellType::PhaseConserving { c: 0.3 };
-            },
-            3 => {
-                s.filter_type = MaxwellType::Diode;
+    /// Initiates a new run, with a fresh seed unless the seed is fixed
+    fn regenerate(&mut self){
+        if self.randomize_seed{
+            self.parameters.seed = rand::random();
+        }
+        self.initiate();
+    }
+
+    /// Continues the simulation from the `index`-th snapshot, forgetting everything after it
+    fn resume_from(&mut self, index: usize){
+        if let Some(snapshot) = self.history.get(index){
+            self.simulation = snapshot.clone();
+            self.history.truncate(index);
+            self.recorder.truncate(self.simulation.time);
+            self.recorder.restart_window(&self.simulation);
+            self.diagnostics.rewind(&self.simulation);
+            self.trails.clear();
+        }
+        self.rewind = None;
+    }
+
+    /// The simulation that should be shown: either the present one or an inspected snapshot
+    fn shown_simulation(&self) -> &Simulation{
+        self.rewind.and_then(|i| self.history.get(i)).unwrap_or(&self.simulation)
+    }
+
+    /// Advances the simulation by `steps` time steps of `dt`,
+    /// averaging the observables over `measure_time`
+    fn advance(&mut self, steps: u32){
+        self.recorder.window = self.measure_time;
+        let style = self.style();
+        // the simulation may have been restarted or rewound while recording
+        self.next_frame = self.next_frame.min(self.simulation.time + self.recording.time_per_frame);
+        for _ in 0..steps{
+            self.simulation.step(self.dt);
+            self.recorder.observe(&self.simulation, self.dt as f64);
+            self.diagnostics.observe(&self.simulation);
+            // a frame every `time_per_frame` of simulated time, like `Run::record`,
+            // with half a step of slack, so rounding of `dt` doesn't delay a frame by a step
+            if let Some(scenes) = &mut self.recorded && scenes.len() < MAX_RECORDED_FRAMES
+                && self.simulation.time + self.dt as f64*0.5 >= self.next_frame{
+                scenes.push(self.simulation.scene(style));
+                self.next_frame += self.recording.time_per_frame;
+            }
+        }
+        self.history.record(&self.simulation);
+        if self.svg.trails{
+            self.trails.push(&self.simulation.scene(style));
+        }
+        if let Some(ensemble) = &mut self.ensemble{
+            ensemble.advance(steps as f64*self.dt as f64);
+        }
+    }
+
+    /// Starts replicas of the current run, seeded with consecutive seeds from the one after its own,
+    /// so none of them repeats the shown run
+    fn start_ensemble(&mut self){
+        let mut scenario = Scenario{parameters: self.running.clone(), ..self.scenario()};
+        scenario.parameters.seed = scenario.parameters.seed.wrapping_add(1);
+        self.ensemble = match Ensemble::new(&scenario, self.replicas){
+            Ok(ensemble) => {
+                // the replicas catch up with the run in the background
+                let mut ensemble = Background::new(ensemble);
+                ensemble.advance(self.simulation.time);
+                Some(ensemble)
             },
-            4 => {
-                s.filter_type = MaxwellType::Empty;
+            Err(e) => {
+                self.initiation_status = format!("Can't start the ensemble: {e}");
+                None
+            }
+        };
+    }
+
+    fn style(&self) -> Style{
+        Style{dark_mode: self.dark_mode, colouring: self.colouring}
+    }
+
+    /// Shows where the file went or why it didn't
+    fn report(&mut self, saved: Result<String, String>){
+        self.status = match saved{
+            Ok(path) => format!("Saved to {path}"),
+            Err(e) => format!("Export failed: {e}")
+        };
+    }
+
+    /// Saves the shown state as a single PNG image
+    fn save_frame(&mut self){
+        let scene = self.shown_simulation().scene(self.style());
+        let name = format!("maxwell_{}_{:.2}.png", self.running.seed, self.shown_simulation().time);
+        let png = Image::render(&scene, self.recording.width, self.recording.height).and_then(|image| image.to_png());
+        self.report(png.and_then(|png| files::save(&name, "image/png", &png)));
+    }
+
+    /// Saves the shown state as a vector picture
+    fn save_svg(&mut self){
+        let shown = self.shown_simulation();
+        let contents = svg::to_svg(&shown.scene(self.style()), Some(&self.trails), &self.svg);
+        let name = format!("maxwell_{}_{:.2}.svg", self.running.seed, shown.time);
+        self.report(files::save(&name, "image/svg+xml", contents.as_bytes()));
+    }
+
+    /// Renders the captured frames one by one into an animated PNG
+    /// (and into a sequence of PNG files, if asked to) and saves it
+    fn save_recording(&mut self){
+        let Some(scenes) = self.recorded.take() else{
+            return
+        };
+        let (width, height) = (self.recording.width, self.recording.height);
+        let mut apng = vec![];
+        let encoded = ApngEncoder::new(&mut apng, width, height, scenes.len() as u32, self.recording.fps).and_then(|mut encoder| {
+            #[cfg(not(target_arch = "wasm32"))]
+            let mut sequence = self.png_sequence
+                .then(|| files::Sequence::new(&format!("maxwell_{}_frames", self.running.seed), "png"))
+                .transpose()?;
+            for scene in &scenes{
+                let image = Image::render(scene, width, height)?;
+                encoder.write_frame(&image)?;
+                #[cfg(not(target_arch = "wasm32"))]
+                if let Some(sequence) = &mut sequence{
+                    sequence.save(&image.to_png()?)?;
+                }
+            }
+            encoder.finish()
+        });
+        let name = format!("maxwell_{}.png", self.running.seed);
+        self.report(encoded.and_then(|_| files::save(&name, "image/apng", &apng)));
+    }
+
+    /// Saves the recorded observables, remembering the outcome in `status`
+    fn export(&mut self, extension: &str){
+        let (contents, mime) = match extension{
+            "csv" => (self.recorder.to_csv(&self.running, self.dt), "text/csv"),
+            _ => (self.recorder.to_json(&self.running, self.dt), "application/json"),
+        };
+        let name = format!("maxwell_{}.{}", self.running.seed, extension);
+        self.report(files::save(&name, mime, contents.as_bytes()));
+    }
+
+    /// Takes over the settings of `scenario`, without starting it
+    fn apply_scenario(&mut self, scenario: &Scenario){
+        self.parameters = scenario.parameters.clone();
+        self.dt = scenario.dt;
+        self.measure_time = scenario.window;
+        self.recorder.observables = scenario.observables.clone();
+    }
+
+    /// Current settings as a scenario
+    fn scenario(&self) -> Scenario{
+        Scenario{
+            name: "Custom".into(),
+            parameters: self.parameters.clone(),
+            dt: self.dt,
+            window: self.measure_time,
+            observables: self.recorder.observables.clone(),
+            ..Scenario::default()
+        }
+    }
+
+    /// Copies a link reproducing the current run, seed included
+    fn copy_link(&mut self, ctx: &egui::Context){
+        let scenario = Scenario{parameters: self.running.clone(), ..self.scenario()};
+        let fragment = scenario.to_fragment();
+        ctx.copy_text(format!("{}#{}", self.page_url, fragment));
+        // also keep it in the address bar, so reloading the page restores the run
+        #[cfg(target_arch = "wasm32")]
+        if let Some(window) = web_sys::window(){
+            let _ = window.location().set_hash(&fragment);
+        }
+        self.link_status = "Link copied".into();
+    }
+
+    /// Starts a scenario loaded by the user, with its own seed
+    fn load_scenario(&mut self, scenario: Result<Scenario, String>){
+        match scenario{
+            Ok(scenario) => {
+                self.apply_scenario(&scenario);
+                self.randomize_seed = false;
+                self.initiate();
+                self.scenario_status = format!("Loaded \"{}\"", scenario.name);
             },
-            _ => unreachable!()
+            Err(e) => self.scenario_status = e
         }
-        s.initiate();
+    }
+
+    /// Set preset `n`, one of the bundled scenarios
+    fn set(n: u8) -> Self{
+        let mut s = Self::default();
+        let (name, _) = BUNDLED[n as usize - 1];
+        s.apply_scenario(&Scenario::bundled(name).expect("Bundled scenarios exist"));
+        s.regenerate();
         s
     }
 }
@@ -112,17 +421,44 @@ impl eframe::App for TemplateApp {
     /// Called each time the UI needs repainting, which may be many times per second.
     /// Put your widgets into a `SidePanel`, `TopPanel`, `CentralPanel`, `Window` or `Area`.
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // the checks of the demon go on a part every frame, so no frame waits for all of them
+        if let Some(check) = &mut self.balance && !check.is_done(){
+            check.run(BALANCE_SAMPLES/CHECK_FRAMES);
+            ctx.request_repaint();
+        }
+        if let Some((_, Ok(check))) = &mut self.phase_space && !check.is_done(){
+            check.run(PHASE_SPACE_SAMPLES/CHECK_FRAMES);
+            ctx.request_repaint();
+        }
+        if let Some(ensemble) = &mut self.ensemble{
+            ensemble.poll();
+            if !ensemble.caught_up(){
+                ctx.request_repaint();
+            }
+        }
 
         // Examples of how to create different panels and windows.
         // Pick whichever suits you.
         // Tip: a good default choice is to just keep the `CentralPanel`.
         // For inspiration and more examples, go to https://emilk.github.io/egui
 
-        #[cfg(not(target_arch = "wasm32"))] // no File->Quit on web pages!
         egui::TopBottomPanel::top("panel").show(ctx, |ui| {
             // The top panel is often a good place for a menu bar:
             egui::MenuBar::new().ui(ui, |ui| {
                 ui.menu_button("File", |ui| {
+                    if ui.button("Export SVG").clicked() {
+                        self.save_svg();
+                    }
+                    if ui.button("Save frame as PNG").clicked() {
+                        self.save_frame();
+                    }
+                    if ui.button("Export CSV").clicked() {
+                        self.export("csv");
+                    }
+                    if ui.button("Export JSON").clicked() {
+                        self.export("json");
+                    }
+                    #[cfg(not(target_arch = "wasm32"))] // no File->Quit on web pages!
                     if ui.button("Quit").clicked() {
                         // TODO: FIX!
                         // _frame.close();
@@ -130,73 +466,427 @@ impl eframe::App for TemplateApp {
                 });
             });
         });
-        
-        let mut density: f64 = 0.0;
 
         egui::Window::new("Parameters").show(ctx, |ui| {
 
-            ui.checkbox(&mut self.paused, "Paused");
-            if ui.input(|i| i.key_pressed(egui::Key::Space)) {
-                self.paused = !self.paused;
-            }
-            else if ui.input(|i| i.key_pressed(egui::Key::Num1)) {
-                *self = Self::set(1);
-            }
-            else if ui.input(|i| i.key_pressed(egui::Key::Num2)) {
-                *self = Self::set(2);
-            }
-            else if ui.input(|i| i.key_pressed(egui::Key::Num3)) {
-                *self = Self::set(3);
-            }
-            else if ui.input(|i| i.key_pressed(egui::Key::Num4)) {
-                *self = Self::set(4);
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.paused, "Paused");
+                if ui.add_enabled(self.paused, egui::Button::new("Step once")).clicked(){
+                    self.step_once = true;
+                }
+            });
+            // the keys go to the focused widget instead, e.g. while typing into a text field
+            if !ctx.wants_keyboard_input(){
+                if ui.input(|i| i.key_pressed(egui::Key::Space)) {
+                    self.paused = !self.paused;
+                }
+                else if self.paused && ui.input(|i| i.key_pressed(egui::Key::ArrowRight)) {
+                    self.step_once = true;
+                }
+                else if ui.input(|i| i.key_pressed(egui::Key::Num1)) {
+                    *self = Self::set(1);
+                }
+                else if ui.input(|i| i.key_pressed(egui::Key::Num2)) {
+                    *self = Self::set(2);
+                }
+                else if ui.input(|i| i.key_pressed(egui::Key::Num3)) {
+                    *self = Self::set(3);
+                }
+                else if ui.input(|i| i.key_pressed(egui::Key::Num4)) {
+                    *self = Self::set(4);
+                }
             }
 
 
-            ui.checkbox(&mut self.collisions, "Collisions");
+            ui.checkbox(&mut self.parameters.collisions, "Collisions");
             ui.end_row();
+            ui.add(egui::Slider::new(&mut self.dt, 0.001..=0.05).logarithmic(true).text("Time step"));
+            ui.add(egui::Slider::new(&mut self.steps_per_frame, 1..=100).logarithmic(true).text("Steps per frame"));
             ui.add(egui::Slider::new(&mut self.measure_time, 0.01..=1.0).text("Measuring time"));
-            ui.add(egui::Slider::new(&mut self.temperature, 0.0..=3.0).text("Temperature"));
-            ui.add(egui::Slider::new(&mut self.balls_n, 0..=1000).text("Balls number"));
-            ui.add(egui::Slider::new(&mut self.radius, 0.0..=0.03).text("Ball radius"));
-            ui.add(egui::Slider::new(&mut self.filter_height, 0.0..=1.0).text("Filter height"));
-            ui.add(egui::Slider::new(&mut self.wall_width, 0.0..=0.1).text("Wall width"));
+            ui.add(egui::Slider::new(&mut self.parameters.temperature, 0.0..=3.0).text("Temperature"));
+            if let [species] = &mut self.parameters.species[..]{
+                ui.add(egui::Slider::new(&mut species.count, 0..=1000).text("Balls number"));
+            }
+            else{
+                for species in &mut self.parameters.species{
+                    ui.horizontal(|ui| {
+                        ui.add(egui::Slider::new(&mut species.count, 0..=1000).text(&species.name));
+                        ui.add(egui::DragValue::new(&mut species.mass).range(0.01..=100.0).speed(0.01).prefix("mass "));
+                        ui.add(egui::DragValue::new(&mut species.charge).range(-10.0..=10.0).speed(0.01).prefix("charge "));
+                    });
+                }
+            }
+            ui.add(egui::Slider::new(&mut self.parameters.radius, 0.0..=0.03).text("Ball radius"));
+            ui.add(egui::Slider::new(&mut self.parameters.geometry.filter_height, 0.0..=1.0).text("Filter height"));
+            ui.add(egui::Slider::new(&mut self.parameters.geometry.wall_width, 0.0..=0.1).text("Wall width"));
+            egui::CollapsingHeader::new("Initial condition").show(ui, |ui| {
+                let temperature = self.parameters.temperature;
+                let initial = &mut self.parameters.initial;
+                egui::ComboBox::from_label("Start")
+                    .selected_text(match initial {
+                        InitialCondition::Uniform => "Uniform",
+                        InitialCondition::OneChamber {..} => "One chamber",
+                        InitialCondition::TwoTemperatures {..} => "Two temperatures",
+                        InitialCondition::Monoenergetic => "Monoenergetic",
+                        InitialCondition::Lattice => "Lattice",
+                        InitialCondition::File {..} => "From file",
+                        InitialCondition::Given {..} => "Given balls",
+                    })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(initial, InitialCondition::Uniform, "Uniform");
+                        ui.selectable_value(initial, InitialCondition::OneChamber { left: true }, "One chamber")
+                            .on_hover_text("For a free expansion");
+                        ui.selectable_value(initial, InitialCondition::TwoTemperatures { left: temperature*2.0, right: temperature/2.0 }, "Two temperatures");
+                        ui.selectable_value(initial, InitialCondition::Monoenergetic, "Monoenergetic")
+                            .on_hover_text("The same speed for all balls of a species, to watch them relax to Maxwell's distribution");
+                        ui.selectable_value(initial, InitialCondition::Lattice, "Lattice");
+                        ui.selectable_value(initial, InitialCondition::File { path: "balls.csv".into() }, "From file")
+                            .on_hover_text("One ball a line: x, y, vx, vy and optionally the species index");
+                    }
+                );
+                match initial{
+                    InitialCondition::OneChamber { left } => {
+                        ui.checkbox(left, "Left chamber");
+                    },
+                    InitialCondition::TwoTemperatures { left, right } => {
+                        ui.add(egui::Slider::new(left, 0.0..=5.0).text("Left temperature"));
+                        ui.add(egui::Slider::new(right, 0.0..=5.0).text("Right temperature"));
+                    },
+                    InitialCondition::File { path } => {
+                        ui.horizontal(|ui| {
+                            ui.label("Path");
+                            ui.text_edit_singleline(path);
+                        });
+                        #[cfg(not(target_arch = "wasm32"))]
+                        if ui.button("Load").clicked(){
+                            match initial.load(){
+                                Ok(loaded) => {
+                                    *initial = loaded;
+                                    self.initiation_status.clear();
+                                },
+                                Err(e) => self.initiation_status = format!("Can't load the balls: {e}")
+                            }
+                        }
+                        #[cfg(target_arch = "wasm32")]
+                        ui.label("Files can be read only by the native app, the web one can start from given balls of a scenario");
+                    },
+                    InitialCondition::Given { balls } => {
+                        ui.label(format!("{} balls", balls.len()));
+                    },
+                    _ => {}
+                }
+            });
+            egui::CollapsingHeader::new("External field").show(ui, |ui| {
+                let field = &mut self.parameters.field;
+                ui.add(egui::Slider::new(&mut field.gravity, 0.0..=20.0).text("Gravity"));
+                ui.add(egui::Slider::new(&mut field.electric[0], -10.0..=10.0).text("Electric field x"));
+                ui.add(egui::Slider::new(&mut field.electric[1], -10.0..=10.0).text("Electric field y"));
+                if let [species] = &mut self.parameters.species[..]{
+                    ui.add(egui::Slider::new(&mut species.charge, -5.0..=5.0).text("Charge"));
+                }
+                egui::ComboBox::from_label("Potential")
+                    .selected_text(match field.potential {
+                        ExternalPotential::None => "None",
+                        ExternalPotential::Harmonic {..} => "Harmonic",
+                        ExternalPotential::Periodic {..} => "Periodic",
+                    })
+                    .show_ui(ui, |ui| {
+                        let potential = &mut field.potential;
+                        ui.selectable_value(potential, ExternalPotential::None, "None");
+                        ui.selectable_value(potential, ExternalPotential::Harmonic { stiffness: 10.0, center: [0.5, 0.5] }, "Harmonic");
+                        ui.selectable_value(potential, ExternalPotential::Periodic { amplitude: 0.5, wavelength: 0.25 }, "Periodic");
+                    }
+                );
+                match &mut field.potential{
+                    ExternalPotential::None => {},
+                    ExternalPotential::Harmonic { stiffness, center } => {
+                        ui.add(egui::Slider::new(stiffness, 0.0..=100.0).text("Stiffness"));
+                        ui.add(egui::Slider::new(&mut center[0], 0.0..=1.0).text("Center x"));
+                        ui.add(egui::Slider::new(&mut center[1], 0.0..=1.0).text("Center y"));
+                    },
+                    ExternalPotential::Periodic { amplitude, wavelength } => {
+                        ui.add(egui::Slider::new(amplitude, 0.0..=5.0).text("Amplitude"));
+                        ui.add(egui::Slider::new(wavelength, 0.05..=1.0).text("Wavelength"));
+                    },
+                }
+            });
+            egui::CollapsingHeader::new("Thermal walls").show(ui, |ui| {
+                let walls = &mut self.parameters.thermal_walls;
+                for (wall, name) in [(&mut walls.left, "Left"), (&mut walls.right, "Right")]{
+                    ui.horizontal(|ui| {
+                        let mut thermal = wall.is_some();
+                        if ui.checkbox(&mut thermal, name).changed(){
+                            *wall = thermal.then_some(self.parameters.temperature);
+                        }
+                        if let Some(t) = wall{
+                            ui.add(egui::Slider::new(t, 0.0..=5.0).text("Temperature"));
+                        }
+                    });
+                }
+            });
+            egui::CollapsingHeader::new("Ratchet").show(ui, |ui| {
+                let mut enabled = self.parameters.ratchet.is_some();
+                if ui.checkbox(&mut enabled, "Ratchet and pawl").changed(){
+                    self.parameters.ratchet = enabled.then(RatchetParameters::default);
+                }
+                if let Some(ratchet) = &mut self.parameters.ratchet{
+                    ui.add(egui::Slider::new(&mut ratchet.vanes, 1..=12).text("Vanes"));
+                    ui.add(egui::Slider::new(&mut ratchet.vane_length, 0.02..=0.2).text("Vane length"));
+                    ui.add(egui::Slider::new(&mut ratchet.inertia, 0.0005..=0.1).logarithmic(true).text("Inertia"));
+                    ui.add(egui::Slider::new(&mut ratchet.teeth, 1..=24).text("Teeth"));
+                    ui.add(egui::Slider::new(&mut ratchet.tooth_height, 0.05..=1.0).text("Tooth height"));
+                    ui.add(egui::Slider::new(&mut ratchet.steep, 0.05..=0.5).text("Steep face"));
+                    ui.add(egui::Slider::new(&mut ratchet.pawl_inertia, 0.0005..=0.1).logarithmic(true).text("Pawl inertia"));
+                    ui.add(egui::Slider::new(&mut ratchet.spring, 0.0..=50.0).text("Pawl spring"));
+                    ui.add(egui::Slider::new(&mut ratchet.load, -0.05..=0.05).text("Load"));
+                }
+            });
+            egui::CollapsingHeader::new("Szilard engine").show(ui, |ui| {
+                let mut enabled = self.parameters.szilard.is_some();
+                if ui.checkbox(&mut enabled, "Single ball engine").on_hover_text("Needs exactly one ball").changed(){
+                    self.parameters.szilard = enabled.then(SzilardParameters::default);
+                }
+                if let Some(szilard) = &mut self.parameters.szilard{
+                    ui.add(egui::Slider::new(&mut szilard.piston_speed, 0.005..=1.0).logarithmic(true).text("Piston speed"));
+                    ui.add(egui::Slider::new(&mut szilard.idle, 0.0..=5.0).text("Idle time"));
+                }
+            });
+            egui::CollapsingHeader::new("Control").show(ui, |ui| {
+                let control = &mut self.parameters.control;
+                egui::ComboBox::from_label("Demon in time")
+                    .selected_text(match control {
+                        None => "Constant",
+                        Some(Control::Schedule {..}) => "Schedule",
+                        Some(Control::Feedback {..}) => "Feedback",
+                    })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(control, None, "Constant");
+                        ui.selectable_value(control, Some(Control::Schedule { open: 1.0, closed: 1.0 }), "Schedule")
+                            .on_hover_text("A door opening the demon and closing it in turns");
+                        ui.selectable_value(control, Some(Control::Feedback { target: 0.3, below: MaxwellType::Wall, above: MaxwellType::Diode }), "Feedback")
+                            .on_hover_text("Switches the demon by the chamber counts");
+                    }
+                );
+                match control{
+                    None => {},
+                    Some(Control::Schedule { open, closed }) => {
+                        ui.add(egui::Slider::new(open, 0.0..=10.0).text("Open for"));
+                        ui.add(egui::Slider::new(closed, 0.0..=10.0).text("Closed for"));
+                    },
+                    Some(Control::Feedback { target, below, above }) => {
+                        ui.add(egui::Slider::new(target, 0.0..=1.0).text("Target left fraction"));
+                        for (label, demon) in [("Below the target", below), ("Above the target", above)]{
+                            egui::ComboBox::from_label(label)
+                                .selected_text(format!("{demon:?}"))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(demon, MaxwellType::Empty, "Empty");
+                                    ui.selectable_value(demon, MaxwellType::Wall, "Wall");
+                                    ui.selectable_value(demon, MaxwellType::Diode, "Diode");
+                                }
+                            );
+                        }
+                    },
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.randomize_seed, "Random seed");
+                ui.add_enabled(!self.randomize_seed, egui::DragValue::new(&mut self.parameters.seed));
+            });
+
+            egui::ComboBox::from_label("Interaction")
+                .selected_text(match self.parameters.interaction {
+                    Interaction::HardDisks => "Hard disks",
+                    Interaction::LennardJones {..} => "Lennard-Jones",
+                    Interaction::Wca {..} => "WCA",
+                    Interaction::SoftSphere {..} => "Soft spheres",
+                })
+                .show_ui(ui, |ui| {
+                    let interaction = &mut self.parameters.interaction;
+                    ui.selectable_value(interaction, Interaction::HardDisks, "Hard disks");
+                    ui.selectable_value(interaction, Interaction::LennardJones { epsilon: 1.0, cutoff: 2.5 }, "Lennard-Jones");
+                    ui.selectable_value(interaction, Interaction::Wca { epsilon: 1.0 }, "WCA");
+                    ui.selectable_value(interaction, Interaction::SoftSphere { epsilon: 1.0, exponent: 12.0, cutoff: 2.0 }, "Soft spheres");
+                }
+            );
+            match &mut self.parameters.interaction{
+                Interaction::HardDisks => {},
+                Interaction::LennardJones { epsilon, cutoff } => {
+                    ui.add(egui::Slider::new(epsilon, 0.0..=5.0).text("Epsilon"));
+                    ui.add(egui::Slider::new(cutoff, 1.5..=4.0).text("Cut-off, diameters"));
+                },
+                Interaction::Wca { epsilon } => {
+                    ui.add(egui::Slider::new(epsilon, 0.0..=5.0).text("Epsilon"));
+                },
+                Interaction::SoftSphere { epsilon, exponent, cutoff } => {
+                    ui.add(egui::Slider::new(epsilon, 0.0..=5.0).text("Epsilon"));
+                    ui.add(egui::Slider::new(exponent, 1.0..=24.0).text("Exponent"));
+                    ui.add(egui::Slider::new(cutoff, 1.0..=4.0).text("Cut-off, diameters"));
+                },
+            }
 
             egui::ComboBox::from_label("Filter type:")
-                .selected_text(match self.filter_type {
+                .selected_text(match self.parameters.filter_type {
                     MaxwellType::Diode => "Diode",
                     MaxwellType::Temperature {..} => "Temperature",
                     MaxwellType::Tennis => "Tennis",
                     MaxwellType::Empty => "Empty",
+                    MaxwellType::Wall => "Wall",
                     MaxwellType::PhaseConserving {..} => "Phase conserving",
+                    MaxwellType::Informed {..} => "Informed",
+                    MaxwellType::Stochastic(_) => "Stochastic",
+                    MaxwellType::Graded {..} => "Graded",
+                    MaxwellType::Mirror {..} => "Mirror",
                 })
                 .show_ui(ui, |ui| {
-                    ui.selectable_value(&mut self.filter_type, MaxwellType::Diode, "Diode");
-                    ui.selectable_value(&mut self.filter_type, MaxwellType::Temperature { t: self.filter_temperature}, "Temperature");
-                    ui.selectable_value(&mut self.filter_type, MaxwellType::Tennis, "Tennis");
-                    ui.selectable_value(&mut self.filter_type, MaxwellType::PhaseConserving { c: self.filter_constant }, "Phase conserving");
-                    ui.selectable_value(&mut self.filter_type, MaxwellType::Empty, "Empty");
+                    let filter_type = &mut self.parameters.filter_type;
+                    let mean_energy = self.parameters.temperature/2.0;
+                    ui.selectable_value(filter_type, MaxwellType::Diode, "Diode");
+                    ui.selectable_value(filter_type, MaxwellType::Temperature { fast: mean_energy, slow: mean_energy, energy: Energy::Kinetic }, "Temperature")
+                        .on_hover_text("Fast balls pass to the right, slow ones to the left");
+                    ui.selectable_value(filter_type, MaxwellType::Tennis, "Tennis");
+                    ui.selectable_value(filter_type, MaxwellType::PhaseConserving { c: self.filter_constant }, "Phase conserving");
+                    ui.selectable_value(filter_type, MaxwellType::Empty, "Empty");
+                    ui.selectable_value(filter_type, MaxwellType::Wall, "Wall");
+                    // Landauer's bound at the gas temperature
+                    let erasure = self.parameters.temperature/2.0*std::f32::consts::LN_2;
+                    ui.selectable_value(filter_type, MaxwellType::Informed { capacity: 64, erasure, from_gas: false }, "Informed");
+                    ui.selectable_value(filter_type, MaxwellType::Stochastic(Kernel::Transmission { right: 1.0, left: 0.5 }), "Stochastic");
+                    ui.selectable_value(filter_type, MaxwellType::Graded { bottom: -0.5, top: 0.5 }, "Graded");
+                    ui.selectable_value(filter_type, MaxwellType::Mirror { bottom: -0.3, top: 0.3 }, "Mirror");
                 }
             );
 
-            if let MaxwellType::Temperature { t } = &mut self.filter_type{
-                ui.add(egui::Slider::new(t, 0.0..=5.0).text("Filter temperature"));
+            if let MaxwellType::Temperature { fast, slow, energy } = &mut self.parameters.filter_type{
+                ui.add(egui::Slider::new(fast, 0.0..=5.0).text("Passing right above"));
+                ui.add(egui::Slider::new(slow, 0.0..=5.0).text("Passing left below"));
+                ui.horizontal(|ui| {
+                    ui.label("Measuring");
+                    ui.radio_value(energy, Energy::Kinetic, "kinetic energy");
+                    ui.radio_value(energy, Energy::Normal, "normal part")
+                        .on_hover_text("Energy of the motion across the partition");
+                });
             }
-            else if let MaxwellType::PhaseConserving { c } = &mut self.filter_type{
+            else if let MaxwellType::PhaseConserving { c } = &mut self.parameters.filter_type{
                 ui.add(egui::Slider::new(c, 0.0..=1.0).text("Filter constant"));
             }
+            else if let MaxwellType::Informed { capacity, erasure, from_gas } = &mut self.parameters.filter_type{
+                ui.add(egui::Slider::new(capacity, 1..=1024).logarithmic(true).text("Tape bits"));
+                ui.add(egui::Slider::new(erasure, 0.0..=2.0).text("Erasure cost a bit"));
+                ui.checkbox(from_gas, "Paid by the gas").on_hover_text("Otherwise a heat bath pays for the erasures");
+            }
+            else if let MaxwellType::Stochastic(kernel) = &mut self.parameters.filter_type{
+                egui::ComboBox::from_label("Kernel")
+                    .selected_text(match kernel {
+                        Kernel::Transmission {..} => "Transmission",
+                        Kernel::Thermalizing {..} => "Thermalizing",
+                    })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(kernel, Kernel::Transmission { right: 1.0, left: 0.5 }, "Transmission");
+                        ui.selectable_value(kernel, Kernel::Thermalizing { temperature: self.parameters.temperature }, "Thermalizing");
+                    }
+                );
+                match kernel{
+                    Kernel::Transmission { right, left } => {
+                        ui.add(egui::Slider::new(right, 0.0..=1.0).text("Passing right"));
+                        ui.add(egui::Slider::new(left, 0.0..=1.0).text("Passing left"));
+                    },
+                    Kernel::Thermalizing { temperature } => {
+                        ui.add(egui::Slider::new(temperature, 0.0..=5.0).text("Kernel temperature"));
+                    },
+                }
+            }
+            else if let MaxwellType::Graded { bottom, top } = &mut self.parameters.filter_type{
+                ui.add(egui::Slider::new(bottom, -1.0..=1.0).text("Constant at the bottom"));
+                ui.add(egui::Slider::new(top, -1.0..=1.0).text("Constant at the top"));
+            }
+            else if let MaxwellType::Mirror { bottom, top } = &mut self.parameters.filter_type{
+                ui.add(egui::Slider::new(bottom, -1.2..=1.2).text("Tilt at the bottom, rad"));
+                ui.add(egui::Slider::new(top, -1.2..=1.2).text("Tilt at the top, rad"));
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Check detailed balance").on_hover_text("Whether the demon could sort the gas at all").clicked(){
+                    let mass = self.parameters.species.first().map_or(1.0, |s| s.mass);
+                    let filter_type = self.parameters.filter_type;
+                    self.balance = Some(BalanceCheck::new(filter_type, self.parameters.temperature, mass, BALANCE_SAMPLES, self.parameters.seed));
+                }
+                if let Some(check) = &self.balance && check.filter_type == self.parameters.filter_type{
+                    if check.is_done(){
+                        let b = check.result();
+                        ui.label(if b.holds(){"holds"}else{"violated"})
+                            .on_hover_text(format!("Violation {:.4}, sampling noise {:.4}", b.violation, b.noise));
+                    }
+                    else{
+                        ui.label(format!("checking, {:.0}%", 100.0*check.progress()));
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Check phase space").on_hover_text("Whether the demon is reversible and keeps the phase-space volume").clicked(){
+                    let mass = self.parameters.species.first().map_or(1.0, |s| s.mass);
+                    let filter_type = self.parameters.filter_type;
+                    let check = PhaseSpaceCheck::new(filter_type, self.parameters.temperature, mass, PHASE_SPACE_SAMPLES, self.parameters.seed);
+                    self.phase_space = Some((filter_type, check));
+                }
+                if let Some((filter_type, result)) = &self.phase_space && *filter_type == self.parameters.filter_type{
+                    match result{
+                        Ok(check) if !check.is_done() => ui.label(format!("checking, {:.0}%", 100.0*check.progress())),
+                        Ok(check) => {
+                            let p = check.result();
+                            ui.label(if p.holds(){"kept"}else{"broken"})
+                                .on_hover_text(format!("Irreversible {:.2}%, Jacobian not 1 for {:.2}%", 100.0*p.irreversible, 100.0*p.compressing))
+                        },
+                        Err(e) => ui.label(e.as_str())
+                    };
+                }
+            });
             
 
-            if ui.button("Regenerate").clicked() {
-                self.simulation.random_initiation(self.balls_n, self.temperature, self.radius, self.filter_height, self.filter_type, self.collisions, self.wall_width);
-                self.points.clear();
-                self.time = 0.0;
+            ui.horizontal(|ui| {
+                if ui.button("Regenerate").clicked() {
+                    self.regenerate();
+                }
+                if ui.button("Copy link").on_hover_text("Link to this run, with its parameters and seed").clicked(){
+                    self.copy_link(ui.ctx());
+                }
+            });
+            if !self.link_status.is_empty(){
+                ui.label(&self.link_status);
+            }
+            if !self.initiation_status.is_empty(){
+                ui.colored_label(ui.visuals().error_fg_color, &self.initiation_status);
             }
 
-            let (left_count, right_symbol) = self.simulation.structure.count_balls(&self.simulation);
-            ui.label(format!("\nLeft side: {} balls,\nRight side: {} balls", left_count, right_symbol));
-            density = (left_count as f64)/((left_count + right_symbol) as f64)*100.0;
-            ui.label(format!("Left chamber density: {:.1} %", density));
+            let shown = self.shown_simulation();
+            let (left_count, right_symbol) = shown.structure.count_balls(shown);
+            ui.label(format!("\nSimulated time: {:.2}", shown.time));
+            ui.label(format!("Left side: {} balls,\nRight side: {} balls", left_count, right_symbol));
+            if left_count + right_symbol > 0{
+                let density = (left_count as f64)/((left_count + right_symbol) as f64)*100.0;
+                ui.label(format!("Left chamber density: {:.1} %", density));
+            }
+            if let Some(ratchet) = &shown.ratchet{
+                ui.label(format!("Ratchet: {:.2} turns, work {:.3}", ratchet.turns(), ratchet.work));
+            }
+            if let Some(tape) = &shown.tape{
+                ui.label(format!("Tape: {}/{} bits, {} written, {} erased", tape.bits.len(), tape.capacity, tape.written, tape.erased));
+                ui.label(format!("Erasures paid by the bath {:.3}, by the gas {:.3}\nStored free energy {:.3}, net {:.3}",
+                    tape.paid_by_bath, tape.paid_by_gas, tape.stored_free_energy(left_count, right_symbol), tape.balance(left_count, right_symbol)));
+            }
+            if let Some(controller) = &shown.control{
+                ui.label(format!("Control: {:?} now, {} readings, {} switches, {:.1} bits", controller.active, controller.readings, controller.switches, controller.information()));
+                ui.label(format!("Information cost {:.3}, stored free energy {:.3}, net {:.3}",
+                    controller.cost(), controller.stored_free_energy(left_count, right_symbol), controller.balance(left_count, right_symbol)));
+            }
+            if let Some(engine) = &shown.szilard{
+                let memory = match engine.memory{
+                    Some(true) => "left",
+                    Some(false) => "right",
+                    None => "erased"
+                };
+                ui.label(format!("Szilard: {} cycles, {} bits, memory: {memory}", engine.cycles, engine.bits));
+                ui.label(format!("Work {:.3}, erasure {:.3} (kT ln 2 = {:.3} a bit)\nNet {:.3}",
+                    engine.work, engine.erasure, engine.bit_energy(), engine.net_work()));
+            }
             ui.add_space(10.0);
 
             ui.with_layout(egui::Layout::bottom_up(egui::Align::LEFT), |ui| {
@@ -215,20 +905,19 @@ impl eframe::App for TemplateApp {
         egui::CentralPanel::default().show(ctx, |ui| {
             // The central panel the region left after adding TopPanel's and SidePanel's
             if !self.paused{
-                self.time += 0.01;
-                self.simulation.step(0.01);
+                if let Some(index) = self.rewind{
+                    self.resume_from(index);
+                }
+                self.advance(self.steps_per_frame);
                 ui.ctx().request_repaint();
-
-                self.current_frames += 1;
-                self.current_sum += density;
-
-                if self.time % (0.01 * self.current_frames as f64) >= self.measure_time{
-                    //if points.last().map_or(true, |p| density != p.1){
-                    self.points.push((self.time, self.current_sum/self.current_frames as f64));
-                    self.current_sum = 0.0;
-                    self.current_frames = 0;
+            }
+            else if self.step_once{
+                if let Some(index) = self.rewind{
+                    self.resume_from(index);
                 }
+                self.advance(1);
             }
+            self.step_once = false;
             let mut rect = ui.available_rect_before_wrap();
             if rect.height() > rect.width(){
                 rect.set_height(rect.width())
@@ -247,18 +936,214 @@ impl eframe::App for TemplateApp {
                 Rect::from_min_max(Pos2::new(0.0, 0.0), Pos2::new(1.0, 1.0)),
                 rect,
             );
-            self.simulation.paint(&painter, to_screen, ui.visuals().dark_mode);
+            self.dark_mode = ui.visuals().dark_mode;
+            self.shown_simulation().paint(&painter, to_screen, self.style());
             painter.rect_stroke(rect, 1.0, Stroke::new(1.0, Color32::from_gray(16)), StrokeKind::Middle);
             // Make sure we allocate what we used (everything)
             ui.expand_to_include_rect(painter.clip_rect());
             egui::warn_if_debug_build(ui);
         });
 
+        egui::Window::new("History").default_open(false).show(ctx, |ui| {
+            let last = self.history.len().saturating_sub(1);
+            let mut index = self.rewind.unwrap_or(last);
+            let history = &self.history;
+            let timeline = egui::Slider::new(&mut index, 0..=last)
+                .text("Timeline")
+                .custom_formatter(|i, _| format!("{:.2}", history.get(i as usize).map_or(0.0, |s| s.time)));
+            if ui.add_enabled(!self.history.is_empty(), timeline).changed(){
+                self.rewind = Some(index);
+                self.paused = true;
+            }
+
+            ui.horizontal(|ui| {
+                if ui.add_enabled(self.rewind.is_some(), egui::Button::new("Replay from here")).clicked(){
+                    self.resume_from(index);
+                    self.paused = false;
+                }
+                if ui.add_enabled(self.rewind.is_some(), egui::Button::new("Back to present")).clicked(){
+                    self.rewind = None;
+                }
+            });
+            if ui.add(egui::Slider::new(&mut self.history_length, 10..=5000).logarithmic(true).text("Snapshots kept")).changed(){
+                self.history.set_capacity(self.history_length);
+            }
+            ui.add(egui::Slider::new(&mut self.history.interval, 0.01..=1.0).logarithmic(true).text("Snapshot interval"));
+        });
+
+        egui::Window::new("Scenario").default_open(false).show(ctx, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for (i, (name, _)) in BUNDLED.iter().enumerate(){
+                    if ui.button(format!("{}: {}", i + 1, name)).clicked(){
+                        *self = Self::set(i as u8 + 1);
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Show current").clicked(){
+                    self.scenario_text = self.scenario().to_toml();
+                }
+                if ui.button("Load from text").clicked(){
+                    self.load_scenario(Scenario::from_toml(&self.scenario_text));
+                }
+                if ui.button("Save").clicked(){
+                    let saved = files::save("scenario.toml", "application/toml", self.scenario().to_toml().as_bytes());
+                    self.scenario_status = saved.map_or_else(|e| format!("Saving failed: {e}"), |path| format!("Saved to {path}"));
+                }
+            });
+            #[cfg(not(target_arch = "wasm32"))]
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut self.scenario_path);
+                if ui.button("Load file").clicked(){
+                    self.load_scenario(Scenario::load(&self.scenario_path));
+                }
+            });
+            if !self.scenario_status.is_empty(){
+                ui.label(&self.scenario_status);
+            }
+            egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                ui.add(egui::TextEdit::multiline(&mut self.scenario_text).code_editor().desired_width(f32::INFINITY));
+            });
+        });
+
+        egui::Window::new("Recording").default_open(false).show(ctx, |ui| {
+            egui::ComboBox::from_label("Colouring")
+                .selected_text(format!("{:?}", self.colouring))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.colouring, Colouring::Plain, "Plain");
+                    ui.selectable_value(&mut self.colouring, Colouring::Speed, "Speed");
+                    ui.selectable_value(&mut self.colouring, Colouring::Chamber, "Chamber");
+                    ui.selectable_value(&mut self.colouring, Colouring::Species, "Species");
+                }
+            );
+            ui.add(egui::Slider::new(&mut self.recording.width, 64..=2048).text("Width"));
+            ui.add(egui::Slider::new(&mut self.recording.height, 64..=2048).text("Height"));
+            ui.add(egui::Slider::new(&mut self.recording.fps, 1..=60).text("Frames per second"));
+            ui.add(egui::Slider::new(&mut self.recording.time_per_frame, 0.01..=1.0).logarithmic(true).text("Simulated time per frame"));
+
+            if ui.button("Save frame as PNG").clicked(){
+                self.save_frame();
+            }
+            ui.horizontal(|ui| {
+                if ui.checkbox(&mut self.svg.trails, "Trails").changed(){
+                    self.trails.clear();
+                }
+                ui.checkbox(&mut self.svg.velocity_arrows, "Velocity arrows");
+                if ui.button("Save frame as SVG").clicked(){
+                    self.save_svg();
+                }
+            });
+            #[cfg(not(target_arch = "wasm32"))]
+            ui.checkbox(&mut self.png_sequence, "Also save separate PNG frames");
+            match &self.recorded{
+                None => if ui.button("Start recording").clicked(){
+                    self.recorded = Some(vec![self.simulation.scene(self.style())]);
+                    self.next_frame = self.simulation.time + self.recording.time_per_frame;
+                },
+                Some(scenes) => {
+                    if scenes.len() < MAX_RECORDED_FRAMES{
+                        ui.label(format!("{} frames captured", scenes.len()));
+                    }
+                    else{
+                        ui.label(format!("{} frames captured, the most a recording can hold", scenes.len()));
+                    }
+                    if ui.button("Stop and save animation").clicked(){
+                        self.save_recording();
+                    }
+                }
+            }
+            if !self.status.is_empty(){
+                ui.label(&self.status);
+            }
+        });
+
+        egui::Window::new("Ensemble").default_open(false).show(ctx, |ui| {
+            ui.add(egui::Slider::new(&mut self.replicas, 2..=64).text("Replicas"));
+            ui.horizontal(|ui| {
+                if ui.button("Start").on_hover_text("Runs replicas of the current run with consecutive seeds").clicked(){
+                    self.start_ensemble();
+                }
+                if ui.add_enabled(self.ensemble.is_some(), egui::Button::new("Stop")).clicked(){
+                    self.ensemble = None;
+                }
+                if let Some(ensemble) = &self.ensemble
+                    && ui.button("Export CSV").clicked(){
+                    let name = format!("maxwell_ensemble_{}.csv", self.running.seed);
+                    let saved = files::save(&name, "text/csv", ensemble.to_csv().as_bytes());
+                    self.report(saved);
+                }
+            });
+            egui::ComboBox::from_label("Observable")
+                .selected_text(self.ensemble_observable.name())
+                .show_ui(ui, |ui| {
+                    for observable in Observable::ALL{
+                        ui.selectable_value(&mut self.ensemble_observable, observable, observable.name());
+                    }
+                });
+            let Some(ensemble) = &self.ensemble else{
+                return
+            };
+            ui.label(format!("{} replicas, simulated time {:.2}", ensemble.replicas(), ensemble.time()));
+            let spread = ensemble.spread(self.ensemble_observable);
+            Plot::new("ensemble").include_x(0.0).show(ui, |plot_ui| {
+                let line = |name: &str, shift: f64| Line::new(name.to_string(),
+                    spread.iter().map(|s| [s.time, s.mean + shift*s.deviation]).collect::<PlotPoints<'_>>());
+                plot_ui.line(line("Mean", 0.0));
+                plot_ui.line(line("Mean + deviation", 1.0));
+                plot_ui.line(line("Mean - deviation", -1.0));
+            });
+        });
+
+        egui::Window::new("Conservation").default_open(false).show(ctx, |ui| {
+            let current = self.diagnostics.current();
+            let (energy, momentum) = self.diagnostics.max_drift();
+            ui.label("Changes not explained by the walls and the demon, relative to the initial values");
+            ui.label(format!("Energy drift: {:.2e} (largest {energy:.2e})", current.energy));
+            ui.label(format!("Momentum drift: {:.2e} (largest {momentum:.2e})", current.momentum[0].hypot(current.momentum[1])));
+            ui.add(egui::Slider::new(&mut self.diagnostics.tolerance, 1e-8..=1e-1).logarithmic(true).text("Tolerance per step"));
+            ui.label(format!("Flagged steps: {}", self.diagnostics.flagged_count()));
+            for step in self.diagnostics.flagged().iter().take(5){
+                ui.label(format!("t = {:.2}: energy {:+.2e}, momentum ({:+.2e}, {:+.2e})", step.time, step.energy, step.momentum[0], step.momentum[1]));
+            }
+            Plot::new("conservation").include_x(0.0).include_y(0.0).show(ui, |plot_ui| {
+                let samples = self.diagnostics.samples();
+                plot_ui.line(Line::new("Energy", samples.iter().map(|s| [s.time, s.energy]).collect::<PlotPoints<'_>>()));
+                plot_ui.line(Line::new("Momentum x", samples.iter().map(|s| [s.time, s.momentum[0]]).collect::<PlotPoints<'_>>()));
+                plot_ui.line(Line::new("Momentum y", samples.iter().map(|s| [s.time, s.momentum[1]]).collect::<PlotPoints<'_>>()));
+            });
+        });
+
         if true {
+            let rewind_time = self.rewind.and_then(|i| self.history.get(i)).map(|s| s.time);
             egui::Window::new("Left density/time").show(ctx, |ui| {
-                Plot::new("data").include_y(50.0).include_x(0.0).show(ui, |plot_ui| plot_ui.line(Line::new("Number of balls",
-                    self.points.iter().map(|&(x, p)| {
-                        [x, p]}).collect::<PlotPoints<'_>>())));
+                ui.horizontal(|ui| {
+                    if ui.button("Export CSV").clicked(){
+                        self.export("csv");
+                    }
+                    if ui.button("Export JSON").clicked(){
+                        self.export("json");
+                    }
+                });
+                if !self.status.is_empty(){
+                    ui.label(&self.status);
+                }
+                let steady = self.recorder.steady_state(Observable::Density);
+                ui.label(match steady{
+                    Some(s) => format!("Steady since t = {:.1}: {:.1} ± {:.1} %", s.relaxation_time, s.mean, s.half_width),
+                    None => "Not steady yet".into()
+                });
+                Plot::new("data").include_y(50.0).include_x(0.0).show(ui, |plot_ui| {
+                    plot_ui.line(Line::new("Number of balls",
+                        self.recorder.samples().iter().filter_map(|s| {
+                            Some([s.time, s.density?])}).collect::<PlotPoints<'_>>()));
+                    if let Some(time) = rewind_time{
+                        plot_ui.vline(VLine::new("Inspected moment", time));
+                    }
+                    if let Some(s) = steady{
+                        plot_ui.vline(VLine::new("Relaxation time", s.relaxation_time));
+                        plot_ui.hline(HLine::new("Steady mean", s.mean));
+                    }
+                });
             });
         }
     }
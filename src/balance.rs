@@ -0,0 +1,351 @@
+use rand::Rng;
+use rand_distr::StandardNormal;
+
+use crate::noise::Noise;
+use crate::physics::{Impact, Kernel, MaxwellType, transfer};
+use crate::vector::{PI, Real, Vector, to_f64};
+
+/// Directions and speed rings velocities are binned into. Both are symmetric under reversal
+const SECTORS: usize = 12;
+/// Upper ends of the speed rings, in units of the thermal speed of one component
+const RINGS: [Real; 3] = [1.0, 1.6, 2.3];
+/// Parts of the aperture the impact positions are binned into
+const BANDS: usize = 4;
+const CELLS: usize = BANDS*SECTORS*(RINGS.len() + 1);
+/// Step of the numerical derivatives of [`phase_space`]
+const STEP: f64 = 1e-3;
+/// Allowed numerical error of the Jacobian and of the reversed passages
+const TOLERANCE: f64 = 1e-2;
+
+/// Result of [`check`]: how far the demon is from detailed balance with the equilibrium gas
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Balance{
+    /// Total variation distance between the binned joint distribution of incoming and outgoing
+    /// velocities and the one of the time-reversed passages, from 0 to 1
+    pub violation: f64,
+    /// Distance expected from sampling alone if the demon were balanced
+    pub noise: f64,
+    pub samples: usize
+}
+
+impl Balance{
+    /// Whether the demon may be in detailed balance, so that it can't create any imbalance
+    /// between the chambers. Otherwise it can, although it still might not
+    pub fn holds(&self) -> bool{
+        self.violation <= 2.0*self.noise
+    }
+}
+
+fn cell(position: Real, v: Vector, sigma: Real) -> usize{
+    let sector = ((v.angle() + PI)/(2.0*PI)*SECTORS as Real) as usize % SECTORS;
+    let ring = RINGS.iter().take_while(|&&edge| v.length() >= edge*sigma).count();
+    let band = ((position*BANDS as Real) as usize).min(BANDS - 1);
+    (band*(RINGS.len() + 1) + ring)*SECTORS + sector
+}
+
+/// The cell of the reversed velocity, at the same place
+fn reversed(cell: usize) -> usize{
+    cell - cell % SECTORS + (cell % SECTORS + SECTORS/2) % SECTORS
+}
+
+/// Where and how a ball of the equilibrium gas with thermal speed `sigma` of one component hits the demon,
+/// with the flux through the partition from either side
+fn incoming(noise: &mut Noise, sigma: Real) -> (Real, Vector){
+    // flux through the partition is the density weighted by the speed across it
+    let across = sigma*(-2.0*(1.0 - noise.random::<Real>()).ln()).sqrt();
+    let side = if noise.random::<bool>(){1.0}else{-1.0};
+    let along = sigma*noise.sample::<Real, StandardNormal>(StandardNormal);
+    (noise.random::<Real>(), Vector::new(side*across, along))
+}
+
+/// Passes a ball through the demon, entering through the face it moves away from
+fn pass(filter_type: MaxwellType, position: Real, speed: Vector, mass: Real, noise: &mut Noise) -> Vector{
+    transfer(filter_type, Impact{position, from_left: speed.x > 0.0}, speed, mass, noise)
+}
+
+/// Checks detailed balance of `filter_type` with a gas of balls of `mass` at `temperature`.
+///
+/// Balls enter the demon with the equilibrium flux through the partition, from either side and all along the aperture.
+/// Detailed balance means every passage `v → v'` happens as often as the reversed one `-v' → -v` at the same place.
+pub fn check(filter_type: MaxwellType, temperature: f32, mass: f32, samples: usize, seed: u64) -> Balance{
+    let mut check = BalanceCheck::new(filter_type, temperature, mass, samples, seed);
+    check.run(samples);
+    check.result()
+}
+
+/// A [`check`] made a part at a time, e.g. over several frames of the app
+#[derive(Debug, Clone)]
+pub struct BalanceCheck{
+    pub filter_type: MaxwellType,
+    pub samples: usize,
+    mass: Real,
+    sigma: Real,
+    noise: Noise,
+    counts: Vec<u32>,
+    done: usize
+}
+
+impl BalanceCheck{
+    pub fn new(filter_type: MaxwellType, temperature: f32, mass: f32, samples: usize, seed: u64) -> Self{
+        // `temperature` is the mean mass times squared speed, split over the two directions
+        let sigma = (temperature as Real/mass as Real/2.0).sqrt().max(Real::EPSILON);
+        Self{filter_type, samples, mass: mass as Real, sigma, noise: Noise::new(seed), counts: vec![0; CELLS*CELLS], done: 0}
+    }
+
+    /// Draws up to `count` more of the samples
+    pub fn run(&mut self, count: usize){
+        for _ in 0..count.min(self.samples - self.done){
+            let (position, incoming) = incoming(&mut self.noise, self.sigma);
+            let outgoing = pass(self.filter_type, position, incoming, self.mass, &mut self.noise);
+            self.counts[cell(position, incoming, self.sigma)*CELLS + cell(position, outgoing, self.sigma)] += 1;
+        }
+        self.done = (self.done + count).min(self.samples);
+    }
+
+    /// Part of the samples drawn, from 0 to 1
+    pub fn progress(&self) -> f64{
+        self.done as f64/self.samples.max(1) as f64
+    }
+
+    pub fn is_done(&self) -> bool{
+        self.done == self.samples
+    }
+
+    /// Result over the samples drawn so far
+    pub fn result(&self) -> Balance{
+        let n = self.done.max(1) as f64;
+        let counts = &self.counts;
+        let (mut violation, mut noise_level) = (0.0, 0.0);
+        for a in 0..CELLS{
+            for b in 0..CELLS{
+                let back = reversed(b)*CELLS + reversed(a);
+                if back == a*CELLS + b{
+                    continue
+                }
+                let (forward, backward) = (counts[a*CELLS + b] as f64, counts[back] as f64);
+                violation += (forward - backward).abs()/n/2.0;
+                // mean absolute difference of two equal Poisson counts
+                noise_level += (2.0/std::f64::consts::PI*(forward + backward)).sqrt()/n/2.0;
+            }
+        }
+        Balance{violation, noise: noise_level, samples: self.done}
+    }
+}
+
+/// Result of [`phase_space`]: how often the demon breaks the rules of reversible dynamics
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct PhaseSpace{
+    /// Fraction of the passages whose reversal doesn't lead back to the reversed incoming ball
+    pub irreversible: f64,
+    /// Fraction of the passages with the Jacobian of the flux phase space not equal to 1
+    pub compressing: f64,
+    /// Passages the Jacobian was found for, away from the jumps of the demon
+    pub smooth: usize,
+    pub samples: usize
+}
+
+impl PhaseSpace{
+    /// Whether the demon is reversible and keeps the phase-space volume, up to numerical errors
+    pub fn holds(&self) -> bool{
+        self.irreversible <= 1e-3 && self.compressing <= 1e-3
+    }
+}
+
+/// A ball passing the demon in the coordinates of the flux phase space:
+/// position along the aperture, sine of the angle to the partition normal, speed, and direction across the partition
+fn coordinates(position: Real, speed: Vector) -> ([f64; 3], bool){
+    let length = to_f64(speed.length());
+    ([to_f64(position), to_f64(speed.y)/length, length], speed.x > 0.0)
+}
+
+/// Outgoing coordinates of the ball at `x` moving right or left, with the direction it leaves in
+fn map(filter_type: MaxwellType, x: [f64; 3], right: bool, mass: Real, noise: &mut Noise) -> ([f64; 3], bool){
+    let across = (1.0 - x[1]*x[1]).max(0.0).sqrt()*x[2];
+    let speed = Vector::new((if right{across}else{-across}) as Real, (x[1]*x[2]) as Real);
+    coordinates(x[0] as Real, pass(filter_type, x[0] as Real, speed, mass, noise))
+}
+
+/// Checks that `filter_type` follows the phase-space rules of reversible dynamics, for balls of `mass` at `temperature`.
+///
+/// In the coordinates of [`coordinates`] the equilibrium flux through the aperture is uniform over the position
+/// and the sine and goes as the speed squared, so the Jacobian including that factor must be 1. The position
+/// enters it as well, since the demon may act differently along the aperture. Random demons have no Jacobian
+pub fn phase_space(filter_type: MaxwellType, temperature: f32, mass: f32, samples: usize, seed: u64) -> Result<PhaseSpace, String>{
+    let mut check = PhaseSpaceCheck::new(filter_type, temperature, mass, samples, seed)?;
+    check.run(samples);
+    Ok(check.result())
+}
+
+/// A [`phase_space`] check made a part at a time, e.g. over several frames of the app
+#[derive(Debug, Clone)]
+pub struct PhaseSpaceCheck{
+    pub filter_type: MaxwellType,
+    pub samples: usize,
+    mass: Real,
+    sigma: Real,
+    noise: Noise,
+    irreversible: usize,
+    compressing: usize,
+    smooth: usize,
+    done: usize
+}
+
+impl PhaseSpaceCheck{
+    /// Fails for the random demons
+    pub fn new(filter_type: MaxwellType, temperature: f32, mass: f32, samples: usize, seed: u64) -> Result<Self, String>{
+        if let MaxwellType::Stochastic(kernel) = filter_type{
+            let name = match kernel{
+                Kernel::Transmission{..} => "transmission",
+                Kernel::Thermalizing{..} => "thermalizing"
+            };
+            return Err(format!("the {name} demon is random, so it has no Jacobian"))
+        }
+        let sigma = (temperature as Real/mass as Real/2.0).sqrt().max(Real::EPSILON);
+        Ok(Self{filter_type, samples, mass: mass as Real, sigma, noise: Noise::new(seed), irreversible: 0, compressing: 0, smooth: 0, done: 0})
+    }
+
+    /// Checks up to `count` more of the samples
+    pub fn run(&mut self, count: usize){
+        for _ in 0..count.min(self.samples - self.done){
+            self.sample();
+        }
+        self.done = (self.done + count).min(self.samples);
+    }
+
+    /// Part of the samples checked, from 0 to 1
+    pub fn progress(&self) -> f64{
+        self.done as f64/self.samples.max(1) as f64
+    }
+
+    pub fn is_done(&self) -> bool{
+        self.done == self.samples
+    }
+
+    /// Result over the samples checked so far
+    pub fn result(&self) -> PhaseSpace{
+        PhaseSpace{
+            irreversible: self.irreversible as f64/self.done.max(1) as f64,
+            compressing: self.compressing as f64/self.smooth.max(1) as f64,
+            smooth: self.smooth,
+            samples: self.done
+        }
+    }
+
+    fn sample(&mut self){
+        let (filter_type, mass, noise) = (self.filter_type, self.mass, &mut self.noise);
+        let (position, speed) = incoming(noise, self.sigma);
+        let (x, right) = coordinates(position, speed);
+        let (y, out) = map(filter_type, x, right, mass, noise);
+
+        let (back, back_right) = map(filter_type, [y[0], -y[1], y[2]], !out, mass, noise);
+        let reversed = [x[0], -x[1], x[2]];
+        if back_right == right || (0..3).any(|k| (back[k] - reversed[k]).abs() > TOLERANCE*(1.0 + reversed[k].abs())){
+            self.irreversible += 1;
+        }
+
+        // the numerical derivatives need a neighbourhood inside the coordinate bounds
+        if x[0] < 2.0*STEP || x[0] > 1.0 - 2.0*STEP || x[1].abs() > 1.0 - 2.0*STEP{
+            return
+        }
+        let mut jacobian = [[0.0; 3]; 3];
+        let mut jumps = false;
+        for k in 0..3{
+            let step = if k == 2{STEP*x[2]}else{STEP};
+            let (mut forward, mut backward) = (x, x);
+            forward[k] += step;
+            backward[k] -= step;
+            let (forward, forward_out) = map(filter_type, forward, right, mass, noise);
+            let (backward, backward_out) = map(filter_type, backward, right, mass, noise);
+            for i in 0..3{
+                let (after, before) = ((forward[i] - y[i])/step, (y[i] - backward[i])/step);
+                // a kink or a jump of the demon is no place to take a derivative at
+                jumps |= forward_out != out || backward_out != out || (after - before).abs() > TOLERANCE*(1.0 + after.abs());
+                jacobian[i][k] = (after + before)/2.0;
+            }
+        }
+        if jumps{
+            return
+        }
+        self.smooth += 1;
+        let [a, b, c] = jacobian;
+        let determinant = a[0]*(b[1]*c[2] - b[2]*c[1]) - a[1]*(b[0]*c[2] - b[2]*c[0]) + a[2]*(b[0]*c[1] - b[1]*c[0]);
+        if (determinant.abs()*(y[2]/x[2]).powi(2) - 1.0).abs() > TOLERANCE{
+            self.compressing += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    const SAMPLES: usize = 200_000;
+
+    fn verdict(filter_type: MaxwellType) -> Balance{
+        check(filter_type, 1.0, 1.0, SAMPLES, 7)
+    }
+
+    #[test]
+    fn sorting_demons_violate_detailed_balance(){
+        for filter_type in [MaxwellType::Diode, MaxwellType::Tennis, MaxwellType::PhaseConserving{c: 0.3}]{
+            let balance = verdict(filter_type);
+            assert!(!balance.holds(), "{filter_type:?}: {balance:?}");
+        }
+    }
+
+    #[test]
+    fn balanced_kernels_keep_detailed_balance(){
+        for filter_type in [
+            MaxwellType::Stochastic(Kernel::Transmission{right: 0.4, left: 0.4}),
+            MaxwellType::Stochastic(Kernel::Thermalizing{temperature: 1.0}),
+            MaxwellType::Empty
+        ]{
+            let balance = verdict(filter_type);
+            assert!(balance.holds(), "{filter_type:?}: {balance:?}");
+        }
+    }
+
+    fn phase(filter_type: MaxwellType) -> PhaseSpace{
+        phase_space(filter_type, 1.0, 1.0, SAMPLES/50, 7).unwrap()
+    }
+
+    #[test]
+    fn graded_demon_is_reversible(){
+        let phase = phase(MaxwellType::Graded{bottom: -0.5, top: 0.5});
+        assert_eq!(phase.irreversible, 0.0, "{phase:?}");
+        assert_eq!(phase.compressing, 0.0, "{phase:?}");
+        assert!(phase.smooth > SAMPLES/100, "{phase:?}");
+        assert!(phase.holds());
+    }
+
+    #[test]
+    fn phase_conserving_demon_keeps_the_volume_but_not_the_reversal(){
+        let phase = phase(MaxwellType::PhaseConserving{c: 0.3});
+        // the balls it reflects instead of shifting their sine past 1
+        assert!(phase.irreversible > 0.05, "{phase:?}");
+        assert_eq!(phase.compressing, 0.0, "{phase:?}");
+        assert!(!phase.holds());
+    }
+
+    #[test]
+    fn tilted_mirror_is_irreversible_at_glancing_balls(){
+        let phase = phase(MaxwellType::Mirror{bottom: -0.3, top: 0.3});
+        // those it would turn across the partition and reflects plainly instead
+        assert!((0.005..0.1).contains(&phase.irreversible), "{phase:?}");
+        assert!(phase.compressing > 0.5, "{phase:?}");
+        assert!(!phase.holds());
+    }
+
+    #[test]
+    fn checks_made_in_parts_agree_with_ones_made_at_once(){
+        let filter_type = MaxwellType::PhaseConserving{c: 0.3};
+        let mut balance = BalanceCheck::new(filter_type, 1.0, 1.0, 10_000, 3);
+        let mut phase = PhaseSpaceCheck::new(filter_type, 1.0, 1.0, 1_000, 3).unwrap();
+        while !balance.is_done() || !phase.is_done(){
+            balance.run(3_000);
+            phase.run(300);
+        }
+        assert_eq!(balance.result(), check(filter_type, 1.0, 1.0, 10_000, 3));
+        assert_eq!(Ok(phase.result()), phase_space(filter_type, 1.0, 1.0, 1_000, 3));
+    }
+}
@@ -0,0 +1,319 @@
+use crate::balance;
+use crate::ensemble::Ensemble;
+use crate::files;
+use crate::headless::Run;
+use crate::raster::{ApngEncoder, RecordingOptions};
+use crate::scenario::{BUNDLED, Scenario};
+use crate::scene::Colouring;
+use crate::svg::{self, SvgOptions};
+use crate::sweep::{Axis, Sweep};
+
+const USAGE: &str = "\
+Usage:
+    maxwells_demon                       start the app
+    maxwells_demon run <scenario> [options]
+    maxwells_demon sweep <scenario> --vary <axis> [--vary <axis>] [options]
+    maxwells_demon ensemble <scenario> [options]
+    maxwells_demon balance <scenario> [--samples <n>]
+                                         check the demon for detailed balance
+                                         and the phase-space rules
+    maxwells_demon list                  list bundled scenarios
+
+<scenario> is a .toml file, or the name of a bundled scenario.
+
+Options of `run`:
+    --duration <time>       simulated time to run, overrides the scenario
+    --seed <n>              random seed, overrides the scenario
+    --csv <file>            write the recorded observables as CSV
+    --json <file>           write the recorded observables as JSON
+    --svg <file>            write the final state as SVG
+    --apng <file>           record an animated PNG of the run
+    --frames <dir>          record the run as a sequence of PNG files
+    --width <px>            frame width, 512 by default
+    --height <px>           frame height, 512 by default
+    --fps <n>               animation frame rate, 30 by default
+    --time-per-frame <t>    simulated time between frames, 0.05 by default
+    --colouring <c>         plain, speed, chamber or species
+    --light                 light colours instead of the dark ones
+
+Options of `sweep`:
+    --vary <axis>           parameter to vary, as name=from:to:count or name=v1,v2,...
+                            names: temperature, radius, filter_height, wall_width,
+                            fast, slow, c (of the demon), balls (of every species)
+    --seeds <n>             runs per grid point, 4 by default
+    --equilibration <time>  simulated time not averaged over, by default every run is
+                            averaged over its detected steady state
+    --duration <time>       simulated time of every run, overrides the scenario; without
+                            --equilibration a run goes on until its observables settle
+    --max-duration <time>   longest a run goes on, 10 times the duration by default
+    --seed <n>              first seed, overrides the scenario
+    --threads <n>           worker threads, all cores by default
+    --csv <file>            write the results there instead of printing them
+
+Options of `ensemble`:
+    --replicas <n>          number of runs with consecutive seeds, 16 by default
+    --duration <time>       simulated time to run, overrides the scenario
+    --seed <n>              first seed, overrides the scenario
+    --threads <n>           worker threads, all cores by default
+    --csv <file>            write the mean and the standard deviation of the
+                            observables over time there instead of printing them
+";
+
+/// Runs the command line interface, `args` not including the program name
+pub fn main(args: &[String]) -> Result<(), String>{
+    match args.first().map(String::as_str){
+        Some("run") => run(&args[1..]),
+        Some("sweep") => sweep(&args[1..]),
+        Some("ensemble") => ensemble(&args[1..]),
+        Some("balance") => balance(&args[1..]),
+        Some("list") => {
+            for (name, _) in BUNDLED{
+                let scenario = Scenario::bundled(name).expect("Listed scenarios exist");
+                println!("{name:20} {}", scenario.name);
+            }
+            Ok(())
+        },
+        Some("help" | "--help" | "-h") => {
+            print!("{USAGE}");
+            Ok(())
+        },
+        _ => Err(format!("Unknown command\n\n{USAGE}"))
+    }
+}
+
+fn parse<T: std::str::FromStr>(option: &str, value: Option<&String>) -> Result<T, String>{
+    let value = value.ok_or(format!("{option} needs a value"))?;
+    value.parse().map_err(|_| format!("Invalid value of {option}: {value}"))
+}
+
+/// Where to write the results of `run`
+#[derive(Default)]
+struct Outputs{
+    csv: Option<String>,
+    json: Option<String>,
+    svg: Option<String>,
+    apng: Option<String>,
+    frames: Option<String>
+}
+
+/// A bundled scenario or a scenario file
+fn scenario(path: Option<&String>) -> Result<Scenario, String>{
+    let path = path.ok_or(format!("No scenario given\n\n{USAGE}"))?;
+    match Scenario::bundled(path){
+        Some(scenario) => Ok(scenario),
+        None => Scenario::load(path)
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String>{
+    let mut scenario = scenario(args.first())?;
+    let mut outputs = Outputs::default();
+    let mut recording = RecordingOptions::default();
+
+    let mut rest = args[1..].iter();
+    while let Some(option) = rest.next(){
+        match option.as_str(){
+            "--duration" => scenario.duration = parse(option, rest.next())?,
+            "--seed" => scenario.parameters.seed = parse(option, rest.next())?,
+            "--csv" => outputs.csv = Some(parse(option, rest.next())?),
+            "--json" => outputs.json = Some(parse(option, rest.next())?),
+            "--svg" => outputs.svg = Some(parse(option, rest.next())?),
+            "--apng" => outputs.apng = Some(parse(option, rest.next())?),
+            "--frames" => outputs.frames = Some(parse(option, rest.next())?),
+            "--width" => recording.width = parse(option, rest.next())?,
+            "--height" => recording.height = parse(option, rest.next())?,
+            "--fps" => recording.fps = parse(option, rest.next())?,
+            "--time-per-frame" => recording.time_per_frame = parse(option, rest.next())?,
+            "--colouring" => recording.style.colouring = match parse::<String>(option, rest.next())?.as_str(){
+                "plain" => Colouring::Plain,
+                "speed" => Colouring::Speed,
+                "chamber" => Colouring::Chamber,
+                "species" => Colouring::Species,
+                other => return Err(format!("Unknown colouring: {other}"))
+            },
+            "--light" => recording.style.dark_mode = false,
+            other => return Err(format!("Unknown option: {other}\n\n{USAGE}"))
+        }
+    }
+    scenario.validate()?;
+    if recording.time_per_frame.is_nan() || recording.time_per_frame <= 0.0{
+        return Err("--time-per-frame must be positive".into())
+    }
+    if recording.width == 0 || recording.height == 0{
+        return Err("--width and --height must be positive".into())
+    }
+
+    let mut run = Run::from_scenario(&scenario)?;
+    // a failed output doesn't stop the others, but fails the command in the end
+    let mut saved = vec![];
+    if outputs.apng.is_some() || outputs.frames.is_some(){
+        // at least the initial state
+        let frames = ((scenario.duration/recording.time_per_frame).ceil() as usize).max(1);
+        let frame_count = u32::try_from(frames).map_err(|_| format!("{frames} frames are too many for an animation"))?;
+        let mut apng = outputs.apng.as_ref().map(|name| files::create(name)
+            .and_then(|file| ApngEncoder::new(file, recording.width, recording.height, frame_count, recording.fps)));
+        let mut sequence = outputs.frames.as_ref().map(|dir| files::Sequence::new(dir, "png"));
+        // every frame goes to the outputs as soon as it's rendered
+        run.record(frames, &recording, |image| {
+            feed(&mut apng, |apng| apng.write_frame(&image));
+            feed(&mut sequence, |sequence| image.to_png().and_then(|png| sequence.save(&png)));
+        })?;
+        if let (Some(name), Some(apng)) = (&outputs.apng, apng){
+            saved.push(report(apng.and_then(ApngEncoder::finish).and_then(|_| files::location(name))));
+        }
+        if let Some(sequence) = sequence{
+            saved.push(report(sequence.and_then(|sequence| sequence.location())));
+        }
+    }
+    else{
+        run.run_for(scenario.duration);
+    }
+
+    let (left, right) = run.simulation.structure.count_balls(&run.simulation);
+    println!("{}: t = {:.2}, left {left}, right {right}", scenario.name, run.simulation.time);
+    for &observable in &scenario.observables{
+        match run.recorder.steady_state(observable){
+            Some(s) => println!("{:18} steady since t = {:.2}: {:.4} ± {:.4}", observable.name(), s.relaxation_time, s.mean, s.half_width),
+            None => println!("{:18} not steady", observable.name())
+        }
+    }
+    if let Some(ratchet) = &run.simulation.ratchet{
+        println!("ratchet: {:.2} turns, work against the load {:.4}", ratchet.turns(), ratchet.work);
+    }
+    if let Some(tape) = &run.simulation.tape{
+        println!("tape: {} bits written, {} erased, paid by the bath {:.4}, by the gas {:.4}, stored free energy {:.4}, net {:.4}",
+            tape.written, tape.erased, tape.paid_by_bath, tape.paid_by_gas, tape.stored_free_energy(left, right), tape.balance(left, right));
+    }
+    if let Some(controller) = &run.simulation.control{
+        println!("control: {} readings, {} switches, {:.1} bits, information cost {:.4}, stored free energy {:.4}, net {:.4}",
+            controller.readings, controller.switches, controller.information(), controller.cost(),
+            controller.stored_free_energy(left, right), controller.balance(left, right));
+    }
+    if let Some(engine) = &run.simulation.szilard{
+        println!("szilard: {} cycles, {} bits, work {:.4}, erasure cost {:.4} (kT ln 2 = {:.4} per bit), net {:.4}",
+            engine.cycles, engine.bits, engine.work, engine.erasure, engine.bit_energy(), engine.net_work());
+    }
+    let (energy, momentum) = run.diagnostics.max_drift();
+    println!("unexplained drift: energy {energy:.2e}, momentum {momentum:.2e}, {} steps flagged", run.diagnostics.flagged_count());
+    if let Some(name) = &outputs.csv{
+        saved.push(report(files::save(name, "text/csv", run.to_csv().as_bytes())));
+    }
+    if let Some(name) = &outputs.json{
+        saved.push(report(files::save(name, "application/json", run.to_json().as_bytes())));
+    }
+    if let Some(name) = &outputs.svg{
+        let contents = svg::to_svg(&run.simulation.scene(recording.style), None, &SvgOptions::default());
+        saved.push(report(files::save(name, "image/svg+xml", contents.as_bytes())));
+    }
+    saved.into_iter().collect()
+}
+
+fn sweep(args: &[String]) -> Result<(), String>{
+    let mut sweep = Sweep::new(scenario(args.first())?, vec![]);
+    let mut csv = None;
+
+    let mut rest = args[1..].iter();
+    while let Some(option) = rest.next(){
+        match option.as_str(){
+            "--vary" => sweep.axes.push(Axis::parse(&parse::<String>(option, rest.next())?)?),
+            "--seeds" => sweep.seeds = parse(option, rest.next())?,
+            "--equilibration" => sweep.equilibration = Some(parse(option, rest.next())?),
+            "--duration" => sweep.scenario.duration = parse(option, rest.next())?,
+            "--max-duration" => sweep.max_duration = Some(parse(option, rest.next())?),
+            "--seed" => sweep.scenario.parameters.seed = parse(option, rest.next())?,
+            "--threads" => sweep.threads = parse(option, rest.next())?,
+            "--csv" => csv = Some(parse::<String>(option, rest.next())?),
+            other => return Err(format!("Unknown option: {other}\n\n{USAGE}"))
+        }
+    }
+
+    let points = sweep.run()?;
+    let contents = sweep.to_csv(&points);
+    match csv{
+        Some(name) => report(files::save(&name, "text/csv", contents.as_bytes())),
+        None => {
+            print!("{contents}");
+            Ok(())
+        }
+    }
+}
+
+fn ensemble(args: &[String]) -> Result<(), String>{
+    let mut scenario = scenario(args.first())?;
+    let mut replicas = 16;
+    let mut threads = None;
+    let mut csv = None;
+
+    let mut rest = args[1..].iter();
+    while let Some(option) = rest.next(){
+        match option.as_str(){
+            "--replicas" => replicas = parse(option, rest.next())?,
+            "--duration" => scenario.duration = parse(option, rest.next())?,
+            "--seed" => scenario.parameters.seed = parse(option, rest.next())?,
+            "--threads" => threads = Some(parse(option, rest.next())?),
+            "--csv" => csv = Some(parse::<String>(option, rest.next())?),
+            other => return Err(format!("Unknown option: {other}\n\n{USAGE}"))
+        }
+    }
+    scenario.validate()?;
+    if replicas < 2{
+        return Err("An ensemble needs at least two replicas".into())
+    }
+
+    let mut ensemble = Ensemble::new(&scenario, replicas)?;
+    if let Some(threads) = threads{
+        ensemble.threads = threads;
+    }
+    ensemble.run_for(scenario.duration);
+    let contents = ensemble.to_csv();
+    match csv{
+        Some(name) => report(files::save(&name, "text/csv", contents.as_bytes())),
+        None => {
+            print!("{contents}");
+            Ok(())
+        }
+    }
+}
+
+fn balance(args: &[String]) -> Result<(), String>{
+    let scenario = scenario(args.first())?;
+    let mut samples = 1_000_000;
+    let mut rest = args[1..].iter();
+    while let Some(option) = rest.next(){
+        match option.as_str(){
+            "--samples" => samples = parse(option, rest.next())?,
+            other => return Err(format!("Unknown option: {other}\n\n{USAGE}"))
+        }
+    }
+    let p = &scenario.parameters;
+    let mass = p.species.first().map_or(1.0, |s| s.mass);
+    let result = balance::check(p.filter_type, p.temperature, mass, samples, p.seed);
+    println!("{:?}: violation {:.5}, sampling noise {:.5}", p.filter_type, result.violation, result.noise);
+    println!("{}", if result.holds(){
+        "detailed balance may hold: the demon can't sort the gas"
+    }
+    else{
+        "detailed balance is violated: the demon can create an imbalance"
+    });
+    match balance::phase_space(p.filter_type, p.temperature, mass, samples/50, p.seed){
+        Ok(phase) => println!("phase space: {:.2}% of passages irreversible, Jacobian not 1 for {:.2}% of {} smooth ones: {}",
+            100.0*phase.irreversible, 100.0*phase.compressing, phase.smooth,
+            if phase.holds(){"the demon may be a reversible one"}else{"no reversible dynamics can do this"}),
+        Err(e) => println!("phase space: {e}")
+    }
+    Ok(())
+}
+
+/// Passes a frame on to an output that is still working, an output stops at its first failure
+fn feed<T>(output: &mut Option<Result<T, String>>, write: impl FnOnce(&mut T) -> Result<(), String>){
+    if let Some(Ok(sink)) = output && let Err(e) = write(sink){
+        *output = Some(Err(e));
+    }
+}
+
+/// Prints where an output was saved, or gives the reason it wasn't
+fn report(saved: Result<String, String>) -> Result<(), String>{
+    let path = saved.map_err(|e| format!("Saving failed: {e}"))?;
+    println!("Saved to {path}");
+    Ok(())
+}
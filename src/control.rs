@@ -0,0 +1,100 @@
+use std::f64::consts::LN_2;
+
+use crate::physics::MaxwellType;
+use crate::tape::sorting_free_energy;
+
+/// How the demon changes in time
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum Control{
+    /// A door acting as the demon for `open` and as a wall for `closed`, over and over
+    Schedule{open: f32, closed: f32},
+    /// Reads the chamber counts every step, and acts as `below` while the left chamber holds
+    /// less than the `target` fraction of the balls, as `above` otherwise
+    Feedback{target: f32, below: MaxwellType, above: MaxwellType}
+}
+
+impl Control{
+    /// Every demon the control may select, `demon` being the one [`Control::Schedule`] opens
+    pub fn demons(self, demon: MaxwellType) -> [MaxwellType; 2]{
+        match self{
+            Control::Schedule{..} => [demon, MaxwellType::Wall],
+            Control::Feedback{below, above, ..} => [below, above]
+        }
+    }
+}
+
+/// Switches the demon as its [`Control`] says, and keeps account of what it learned doing so
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+pub struct Controller{
+    pub control: Control,
+    /// The demon opened by [`Control::Schedule`]
+    pub demon: MaxwellType,
+    /// The demon acting now
+    pub active: MaxwellType,
+    /// Chamber counts read by [`Control::Feedback`]
+    pub readings: u64,
+    /// Times the active demon changed
+    pub switches: u64,
+    /// kT the information is erased at
+    pub kt: f64
+}
+
+impl Controller{
+    pub fn new(control: Control, demon: MaxwellType, kt: f64) -> Self{
+        let mut controller = Self{control, demon, active: demon, readings: 0, switches: 0, kt};
+        controller.active = controller.choose(0.0, 0, 0);
+        controller
+    }
+
+    fn choose(&self, time: f64, left: usize, right: usize) -> MaxwellType{
+        match self.control{
+            Control::Schedule{open, closed} => {
+                let period = (open + closed) as f64;
+                if period <= 0.0 || time.rem_euclid(period) < open as f64{self.demon}else{MaxwellType::Wall}
+            },
+            Control::Feedback{target, below, above} => {
+                let fraction = left as f32/(left + right).max(1) as f32;
+                if fraction < target{below}else{above}
+            }
+        }
+    }
+
+    /// The demon to act at `time`, with `left` and `right` balls in the chambers
+    pub fn update(&mut self, time: f64, left: usize, right: usize) -> MaxwellType{
+        let active = self.choose(time, left, right);
+        if let Control::Feedback{..} = self.control{
+            self.readings += 1;
+        }
+        if active != self.active{
+            self.switches += 1;
+            self.active = active;
+        }
+        active
+    }
+
+    /// Information in the readings, in bits: the entropy of a two-state chain switching as often as they did.
+    /// That's the least memory a record of them takes, a schedule learns nothing
+    pub fn information(&self) -> f64{
+        if self.readings == 0{
+            return 0.0
+        }
+        let p = (self.switches as f64/self.readings as f64).min(1.0);
+        let entropy = |p: f64| if p <= 0.0{0.0}else{-p*p.log2()};
+        self.readings as f64*(entropy(p) + entropy(1.0 - p))
+    }
+
+    /// Landauer cost of erasing the information, kT ln 2 a bit
+    pub fn cost(&self) -> f64{
+        self.kt*LN_2*self.information()
+    }
+
+    /// Free energy stored by sorting the balls, see [`crate::tape::Tape::stored_free_energy`]
+    pub fn stored_free_energy(&self, left: usize, right: usize) -> f64{
+        sorting_free_energy(self.kt, left, right)
+    }
+
+    /// Stored free energy minus the cost of the information, negative when the information paid for the sorting
+    pub fn balance(&self, left: usize, right: usize) -> f64{
+        self.stored_free_energy(left, right) - self.cost()
+    }
+}
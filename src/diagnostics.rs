@@ -0,0 +1,236 @@
+use std::collections::VecDeque;
+
+use crate::physics::{Exchange, Simulation};
+
+/// Change of the energy and the momentum the walls and the demon don't account for
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Drift{
+    pub time: f64,
+    /// Unexplained energy change relative to the initial kinetic plus the absolute potential energy
+    pub energy: f64,
+    /// Unexplained momentum change relative to the momentum scale `sqrt(2 M K)`, `K` the initial kinetic energy
+    pub momentum: [f64; 2]
+}
+
+/// Tracks whether collisions and integration conserve energy and momentum.
+///
+/// Walls, the demon and the external field may legitimately change them,
+/// what they gave is recorded by the simulation and subtracted.
+#[derive(Debug, Clone)]
+pub struct Diagnostics{
+    /// Relative change in one step above which the step is flagged
+    pub tolerance: f64,
+    /// Simulated time between two stored drift samples, doubled whenever [`SAMPLES_KEPT`] are stored
+    pub interval: f64,
+    initial: Exchange,
+    energy_scale: f64,
+    momentum_scale: f64,
+    last: Drift,
+    samples: Vec<Drift>,
+    /// Number of steps flagged up to each sample, so a rewind knows how many came before it
+    flagged_at_samples: Vec<usize>,
+    flagged: Vec<Drift>,
+    flagged_count: usize,
+    /// Times of the latest flagged steps, for rewinds more precise than the samples
+    flagged_times: VecDeque<f64>
+}
+
+/// Flagged steps kept, only the count grows past it
+const FLAGGED_KEPT: usize = 100;
+
+/// Times of flagged steps kept
+const FLAGGED_TIMES_KEPT: usize = 10_000;
+
+/// Simulated time between two drift samples at the start
+const INTERVAL: f64 = 0.1;
+
+/// Drift samples kept, every other one is dropped when there are more
+pub const SAMPLES_KEPT: usize = 10_000;
+
+impl Diagnostics{
+    pub fn new(simulation: &Simulation) -> Self{
+        let mut diagnostics = Self{
+            tolerance: 1e-4,
+            // set by `clear`
+            interval: 0.0,
+            initial: Exchange::default(),
+            energy_scale: 1.0,
+            momentum_scale: 1.0,
+            last: Drift{time: 0.0, energy: 0.0, momentum: [0.0; 2]},
+            samples: vec![],
+            flagged_at_samples: vec![],
+            flagged: vec![],
+            flagged_count: 0,
+            flagged_times: VecDeque::new()
+        };
+        diagnostics.clear(simulation);
+        diagnostics
+    }
+
+    /// Forgets everything, taking `simulation` as the initial state and sampling at the initial interval again
+    pub fn clear(&mut self, simulation: &Simulation){
+        // the balls rather than the species counts, which given balls ignore
+        let mass = simulation.total_mass();
+        self.initial = simulation.total() - simulation.by_walls - simulation.by_demon - simulation.by_field;
+        // the potential energy may be negative, so the scale adds up the magnitudes
+        let kinetic = simulation.total().energy - simulation.potential_energy();
+        self.energy_scale = (kinetic + simulation.potential_energy().abs()).max(f64::MIN_POSITIVE);
+        self.momentum_scale = (2.0*mass*kinetic).sqrt().max(f64::MIN_POSITIVE);
+        self.interval = INTERVAL;
+        self.samples.clear();
+        self.flagged_at_samples.clear();
+        self.flagged.clear();
+        self.flagged_count = 0;
+        self.flagged_times.clear();
+        self.last = self.drift(simulation);
+        self.push_sample(self.last);
+    }
+
+    /// Forgets what happened after `simulation`, an earlier state of the same run
+    pub fn rewind(&mut self, simulation: &Simulation){
+        let kept = self.samples.partition_point(|s| s.time <= simulation.time);
+        self.samples.truncate(kept);
+        self.flagged_at_samples.truncate(kept);
+        self.flagged.retain(|s| s.time <= simulation.time);
+        let later = self.flagged_times.iter().rev().take_while(|&&t| t > simulation.time).count();
+        let all_later_kept = later < self.flagged_times.len() || self.flagged_times.len() == self.flagged_count;
+        self.flagged_count = if all_later_kept{
+            self.flagged_count - later
+        }
+        else{
+            // the count at the last sample misses the steps flagged after it
+            self.flagged_at_samples.last().copied().unwrap_or(0)
+        };
+        self.flagged_times.truncate(self.flagged_times.len() - later);
+        self.last = self.drift(simulation);
+    }
+
+    /// Stores a sample, halving the resolution of the stored ones when there are too many
+    fn push_sample(&mut self, drift: Drift){
+        if self.samples.len() >= SAMPLES_KEPT{
+            let mut i = 0;
+            self.samples.retain(|_| {i += 1; i % 2 == 1});
+            let mut i = 0;
+            self.flagged_at_samples.retain(|_| {i += 1; i % 2 == 1});
+            self.interval *= 2.0;
+        }
+        self.samples.push(drift);
+        self.flagged_at_samples.push(self.flagged_count);
+    }
+
+    fn drift(&self, simulation: &Simulation) -> Drift{
+        let unexplained = simulation.total() - simulation.by_walls - simulation.by_demon - simulation.by_field - self.initial;
+        Drift{
+            time: simulation.time,
+            energy: unexplained.energy/self.energy_scale,
+            momentum: unexplained.momentum.map(|p| p/self.momentum_scale)
+        }
+    }
+
+    /// Checks the state reached after a step, flagging it if the step changed the unexplained drift
+    pub fn observe(&mut self, simulation: &Simulation){
+        let drift = self.drift(simulation);
+        let change = Drift{
+            time: drift.time,
+            energy: drift.energy - self.last.energy,
+            momentum: [drift.momentum[0] - self.last.momentum[0], drift.momentum[1] - self.last.momentum[1]]
+        };
+        if change.energy.abs().max(change.momentum[0].abs()).max(change.momentum[1].abs()) > self.tolerance{
+            self.flagged_count += 1;
+            if self.flagged_times.len() >= FLAGGED_TIMES_KEPT{
+                self.flagged_times.pop_front();
+            }
+            self.flagged_times.push_back(drift.time);
+            if self.flagged.len() < FLAGGED_KEPT{
+                self.flagged.push(change);
+            }
+        }
+        let last_sample = self.samples.last().map_or(f64::NEG_INFINITY, |s| s.time);
+        // half a step of slack, like the recorder
+        if drift.time - last_sample >= self.interval - (drift.time - self.last.time)*0.5{
+            self.push_sample(drift);
+        }
+        self.last = drift;
+    }
+
+    /// Unexplained drift over time, at most [`SAMPLES_KEPT`] samples
+    pub fn samples(&self) -> &[Drift]{
+        &self.samples
+    }
+
+    /// Latest unexplained drift
+    pub fn current(&self) -> Drift{
+        self.last
+    }
+
+    /// Changes of the first flagged steps
+    pub fn flagged(&self) -> &[Drift]{
+        &self.flagged
+    }
+
+    /// Number of steps flagged since the start
+    pub fn flagged_count(&self) -> usize{
+        self.flagged_count
+    }
+
+    /// Largest unexplained relative drift of the energy and of the momentum so far
+    pub fn max_drift(&self) -> (f64, f64){
+        self.samples.iter().chain([&self.last]).fold((0.0, 0.0), |(energy, momentum), s| (
+            energy.max(s.energy.abs()),
+            momentum.max(s.momentum[0].hypot(s.momentum[1]))
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn rewinding_forgets_flagged_steps_after_it(){
+        let mut simulation = Simulation::new();
+        let mut diagnostics = Diagnostics::new(&simulation);
+        // every step flagged
+        diagnostics.tolerance = -1.0;
+        let mut snapshot = None;
+        for step in 0..10{
+            simulation.step(0.01);
+            diagnostics.observe(&simulation);
+            if step == 3{
+                snapshot = Some(simulation.clone());
+            }
+        }
+        assert_eq!(diagnostics.flagged_count(), 10);
+        diagnostics.rewind(&snapshot.unwrap());
+        assert_eq!(diagnostics.flagged_count(), 4);
+    }
+
+    #[test]
+    fn long_runs_keep_bounded_records(){
+        let mut simulation = Simulation::new();
+        let mut diagnostics = Diagnostics::new(&simulation);
+        diagnostics.tolerance = -1.0;
+        diagnostics.interval = 0.0;
+        let mut snapshots = vec![];
+        for step in 0..25_000{
+            simulation.step(0.01);
+            diagnostics.observe(&simulation);
+            if step == 100 || step == 20_000{
+                snapshots.push(simulation.clone());
+            }
+        }
+        assert_eq!(diagnostics.flagged_count(), 25_000);
+        assert!(diagnostics.samples().len() <= SAMPLES_KEPT);
+        assert!(diagnostics.flagged_times.len() <= FLAGGED_TIMES_KEPT);
+
+        // the latest flagged steps are known exactly
+        diagnostics.rewind(&snapshots[1]);
+        assert_eq!(diagnostics.flagged_count(), 20_001);
+        // older ones from the samples
+        diagnostics.rewind(&snapshots[0]);
+        assert!(diagnostics.flagged_count() <= 101);
+
+        diagnostics.clear(&simulation);
+        assert_eq!((diagnostics.interval, diagnostics.samples().len(), diagnostics.flagged_count()), (INTERVAL, 1, 0));
+    }
+}
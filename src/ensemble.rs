@@ -0,0 +1,346 @@
+use std::fmt::Write;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::mpsc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, Instant};
+
+use crate::headless::Run;
+use crate::observables::{Observable, Sample};
+use crate::scenario::Scenario;
+
+/// Mean and standard deviation of an observable over the replicas at one moment
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Spread{
+    pub time: f64,
+    pub mean: f64,
+    pub deviation: f64
+}
+
+/// Independent replicas of a scenario, differing only by their seeds.
+///
+/// Natively the replicas are advanced on all cores. The web build runs them one after another:
+/// threads there need a rebuilt standard library and cross-origin isolation of the page.
+/// The app runs them as a [`Background`] instead, so its frames don't wait for them.
+pub struct Ensemble{
+    pub scenario: Scenario,
+    pub replicas: Vec<Run>,
+    /// Worker threads, all cores by default
+    pub threads: usize
+}
+
+impl Ensemble{
+    /// `count` replicas of `scenario`, seeded with consecutive seeds from the scenario one.
+    /// Fails if the balls of some replica don't fit in the box
+    pub fn new(scenario: &Scenario, count: usize) -> Result<Self, String>{
+        let replicas = (0..count as u64).map(|i| {
+            let mut scenario = scenario.clone();
+            scenario.parameters.seed = scenario.parameters.seed.wrapping_add(i);
+            Run::from_scenario(&scenario)
+        }).collect::<Result<_, _>>()?;
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Ok(Self{scenario: scenario.clone(), replicas, threads})
+    }
+
+    /// Simulated time of the replicas
+    pub fn time(&self) -> f64{
+        self.replicas.first().map_or(0.0, |r| r.simulation.time)
+    }
+
+    /// Runs every replica for `duration` of simulated time
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn run_for(&mut self, duration: f64){
+        let chunk = self.replicas.len().div_ceil(self.threads.max(1)).max(1);
+        std::thread::scope(|scope| {
+            for replicas in self.replicas.chunks_mut(chunk){
+                scope.spawn(move || {
+                    for run in replicas{
+                        run.run_for(duration);
+                    }
+                });
+            }
+        });
+    }
+
+    /// Runs every replica for `duration` of simulated time
+    #[cfg(target_arch = "wasm32")]
+    pub fn run_for(&mut self, duration: f64){
+        for run in &mut self.replicas{
+            run.run_for(duration);
+        }
+    }
+
+    /// Spread of `observable` over the replicas, for every sample all of them recorded
+    pub fn spread(&self, observable: Observable) -> Vec<Spread>{
+        spread(&self.samples(), observable)
+    }
+
+    /// Mean and standard deviation of the scenario observables over time
+    pub fn to_csv(&self) -> String{
+        to_csv(&self.scenario, &self.samples())
+    }
+
+    fn samples(&self) -> Vec<&[Sample]>{
+        self.replicas.iter().map(|r| r.recorder.samples()).collect()
+    }
+}
+
+/// Whether a replica at `time` is still short of `target`, with the half step of slack of [`Run::run_for`]
+fn behind(time: f64, dt: f32, target: f64) -> bool{
+    time + dt as f64*0.5 < target
+}
+
+/// Spread of `observable` over the replicas at their `i`-th sample, over the replicas it's defined in
+fn spread_at(replicas: &[&[Sample]], observable: Observable, i: usize) -> Option<Spread>{
+    let values: Vec<f64> = replicas.iter().filter_map(|r| r[i].get(observable)).collect();
+    if values.is_empty(){
+        return None
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>()/n;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>()/(n - 1.0).max(1.0);
+    Some(Spread{time: replicas[0][i].time, mean, deviation: variance.sqrt()})
+}
+
+/// Samples all the replicas recorded
+fn recorded(replicas: &[&[Sample]]) -> usize{
+    replicas.iter().map(|r| r.len()).min().unwrap_or(0)
+}
+
+fn spread(replicas: &[&[Sample]], observable: Observable) -> Vec<Spread>{
+    (0..recorded(replicas)).filter_map(|i| spread_at(replicas, observable, i)).collect()
+}
+
+fn to_csv(scenario: &Scenario, replicas: &[&[Sample]]) -> String{
+    let mut out = String::new();
+    let _ = writeln!(out, "# parameters = {}", serde_json::to_string(&scenario.parameters).unwrap_or_default());
+    let _ = writeln!(out, "# dt = {}", scenario.dt);
+    let _ = writeln!(out, "# window = {}", scenario.window);
+    let _ = writeln!(out, "# replicas = {}", replicas.len());
+    out.push_str("time");
+    for observable in &scenario.observables{
+        let _ = write!(out, ",{0}_mean,{0}_deviation", observable.name());
+    }
+    out.push('\n');
+    for i in 0..recorded(replicas){
+        let _ = write!(out, "{}", replicas[0][i].time);
+        for &observable in &scenario.observables{
+            // left empty where the observable isn't defined in any replica
+            let _ = match spread_at(replicas, observable, i){
+                Some(s) => write!(out, ",{},{}", s.mean, s.deviation),
+                None => write!(out, ",,")
+            };
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// An [`Ensemble`] advanced in the background, which the app only has to poll.
+///
+/// Natively every worker thread owns a share of the replicas for as long as the ensemble lives,
+/// steps them towards the time the app sets and reports their progress, which
+/// [`Background::poll`] collects without ever waiting for a worker. The web build has no threads,
+/// there [`Background::poll`] steps the replicas for a few milliseconds of every frame instead.
+pub struct Background{
+    pub scenario: Scenario,
+    /// Simulated time the replicas are advanced to
+    target: f64,
+    /// Senders of the targets, a worker stops once its sender is dropped
+    #[cfg(not(target_arch = "wasm32"))]
+    targets: Vec<mpsc::Sender<f64>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    progress: mpsc::Receiver<Vec<Progress>>,
+    /// Simulated time of every replica, as last reported
+    #[cfg(not(target_arch = "wasm32"))]
+    times: Vec<f64>,
+    /// Samples of every replica reported so far
+    #[cfg(not(target_arch = "wasm32"))]
+    samples: Vec<Vec<Sample>>,
+    #[cfg(target_arch = "wasm32")]
+    replicas: Vec<Run>
+}
+
+/// What a replica did since the last report of its worker
+#[cfg(not(target_arch = "wasm32"))]
+struct Progress{
+    replica: usize,
+    time: f64,
+    new_samples: Vec<Sample>
+}
+
+/// Shortest time between two reports of a worker, unless it reached the target
+#[cfg(not(target_arch = "wasm32"))]
+const REPORT_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Steps `replicas`, numbered by the ensemble, towards the latest of `targets`, a step of each at a time,
+/// and reports their progress
+#[cfg(not(target_arch = "wasm32"))]
+fn work(mut replicas: Vec<(usize, Run)>, targets: mpsc::Receiver<f64>, progress: mpsc::Sender<Vec<Progress>>){
+    let mut target = 0.0;
+    let mut reported: Vec<usize> = replicas.iter().map(|(_, run)| run.recorder.samples().len()).collect();
+    let mut last_report = Instant::now();
+    loop{
+        let reached = !replicas.iter().any(|(_, r)| behind(r.simulation.time, r.dt, target));
+        // sleep once the target is reached, until the app moves it
+        let received = if reached{targets.recv().map_err(|_| mpsc::TryRecvError::Disconnected)}else{targets.try_recv()};
+        match received{
+            Ok(next) => target = next,
+            Err(mpsc::TryRecvError::Disconnected) => return,
+            Err(mpsc::TryRecvError::Empty) => {
+                for (_, run) in replicas.iter_mut().filter(|(_, r)| behind(r.simulation.time, r.dt, target)){
+                    run.step();
+                }
+                let reached = !replicas.iter().any(|(_, r)| behind(r.simulation.time, r.dt, target));
+                if !reached && last_report.elapsed() < REPORT_INTERVAL{
+                    continue
+                }
+                let report = replicas.iter().zip(&mut reported).map(|((replica, run), reported)| {
+                    let samples = run.recorder.samples();
+                    let new_samples = samples[*reported..].to_vec();
+                    *reported = samples.len();
+                    Progress{replica: *replica, time: run.simulation.time, new_samples}
+                }).collect();
+                if progress.send(report).is_err(){
+                    return
+                }
+                last_report = Instant::now();
+            }
+        }
+    }
+}
+
+impl Background{
+    /// Moves the replicas of `ensemble` to the background, split among [`Ensemble::threads`] workers
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new(ensemble: Ensemble) -> Self{
+        let target = ensemble.time();
+        let times = ensemble.replicas.iter().map(|r| r.simulation.time).collect();
+        let samples = ensemble.replicas.iter().map(|r| r.recorder.samples().to_vec()).collect();
+        let mut replicas: Vec<_> = ensemble.replicas.into_iter().enumerate().collect();
+        let chunk = replicas.len().div_ceil(ensemble.threads.max(1)).max(1);
+        let (reports, progress) = mpsc::channel();
+        let mut targets = vec![];
+        while !replicas.is_empty(){
+            let share = replicas.drain(..chunk.min(replicas.len())).collect();
+            let (sender, receiver) = mpsc::channel();
+            let reports = reports.clone();
+            std::thread::spawn(move || work(share, receiver, reports));
+            targets.push(sender);
+        }
+        Self{scenario: ensemble.scenario, target, targets, progress, times, samples}
+    }
+
+    /// Keeps the replicas of `ensemble`, to be stepped by [`Background::poll`]
+    #[cfg(target_arch = "wasm32")]
+    pub fn new(ensemble: Ensemble) -> Self{
+        Self{target: ensemble.time(), scenario: ensemble.scenario, replicas: ensemble.replicas}
+    }
+
+    /// Moves the time the replicas are advanced to on by `duration`
+    pub fn advance(&mut self, duration: f64){
+        self.target += duration;
+        #[cfg(not(target_arch = "wasm32"))]
+        for targets in &self.targets{
+            // a worker only stops with the ensemble, so nobody is left to miss the target
+            let _ = targets.send(self.target);
+        }
+    }
+
+    /// Collects what the workers reported, natively.
+    /// On the web lets the replicas catch up with the target for a part of the frame
+    pub fn poll(&mut self){
+        #[cfg(not(target_arch = "wasm32"))]
+        for report in self.progress.try_iter(){
+            for progress in report{
+                self.times[progress.replica] = progress.time;
+                self.samples[progress.replica].extend(progress.new_samples);
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            const BUDGET_MS: f64 = 8.0;
+            let start = js_sys::Date::now();
+            // a step of every replica in turn, so they keep level with each other
+            while js_sys::Date::now() - start < BUDGET_MS{
+                let mut stepped = false;
+                for run in self.replicas.iter_mut().filter(|r| behind(r.simulation.time, r.dt, self.target)){
+                    run.step();
+                    stepped = true;
+                }
+                if !stepped{
+                    break
+                }
+            }
+        }
+    }
+
+    /// Simulated time of every replica, as of the last poll
+    #[cfg(not(target_arch = "wasm32"))]
+    fn times(&self) -> Vec<f64>{
+        self.times.clone()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn times(&self) -> Vec<f64>{
+        self.replicas.iter().map(|r| r.simulation.time).collect()
+    }
+
+    /// Samples of every replica, as of the last poll
+    #[cfg(not(target_arch = "wasm32"))]
+    fn samples(&self) -> Vec<&[Sample]>{
+        self.samples.iter().map(Vec::as_slice).collect()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn samples(&self) -> Vec<&[Sample]>{
+        self.replicas.iter().map(|r| r.recorder.samples()).collect()
+    }
+
+    /// Number of the replicas
+    pub fn replicas(&self) -> usize{
+        self.times().len()
+    }
+
+    /// Simulated time the slowest replica reached
+    pub fn time(&self) -> f64{
+        self.times().into_iter().fold(f64::INFINITY, f64::min)
+    }
+
+    /// Whether every replica reached the target
+    pub fn caught_up(&self) -> bool{
+        !self.times().into_iter().any(|time| behind(time, self.scenario.dt, self.target))
+    }
+
+    /// See [`Ensemble::spread`]
+    pub fn spread(&self, observable: Observable) -> Vec<Spread>{
+        spread(&self.samples(), observable)
+    }
+
+    /// See [`Ensemble::to_csv`]
+    pub fn to_csv(&self) -> String{
+        to_csv(&self.scenario, &self.samples())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn background_reports_what_the_replicas_did(){
+        let scenario = Scenario::default();
+        let mut ensemble = Ensemble::new(&scenario, 3).unwrap();
+        ensemble.run_for(1.0);
+        let mut background = Background::new(Ensemble::new(&scenario, 3).unwrap());
+        background.advance(1.0);
+        let start = std::time::Instant::now();
+        while !background.caught_up(){
+            assert!(start.elapsed().as_secs() < 60, "The workers didn't catch up");
+            std::thread::sleep(std::time::Duration::from_millis(5));
+            background.poll();
+        }
+        assert_eq!(background.replicas(), 3);
+        assert_eq!(background.time(), ensemble.time());
+        assert_eq!(background.to_csv(), ensemble.to_csv());
+    }
+}
@@ -0,0 +1,52 @@
+use crate::vector::{PI, Real, Vector};
+
+/// External forces acting on every ball, integrated with velocity Verlet
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(default)]
+pub struct Field{
+    /// Acceleration pulling the balls to the bottom of the box
+    pub gravity: f32,
+    /// Uniform field, the force on a ball is its [`crate::physics::Species::charge`] times it
+    pub electric: [f32; 2],
+    pub potential: ExternalPotential
+}
+
+/// Position-dependent potential energy of a ball, the same for every species
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum ExternalPotential{
+    #[default]
+    None,
+    /// `k|r - center|²/2`
+    Harmonic{stiffness: f32, center: [f32; 2]},
+    /// `A sin(2πx/λ)`, a washboard along the box
+    Periodic{amplitude: f32, wavelength: f32}
+}
+
+impl Field{
+    pub fn is_active(&self) -> bool{
+        *self != Field::default()
+    }
+
+    /// Potential energy of a ball at `position` in a box of `height` and the force on it
+    pub fn at(&self, position: Vector, height: Real, mass: Real, charge: Real) -> (Real, Vector){
+        let gravity = self.gravity as Real;
+        let electric = Vector::new(self.electric[0] as Real, self.electric[1] as Real);
+        // heights are measured from the bottom, so the energy stays positive
+        let mut energy = mass*gravity*(height - position.y) - charge*electric.dot(position);
+        let mut force = Vector::new(0.0, mass*gravity) + electric*charge;
+        match self.potential{
+            ExternalPotential::None => {},
+            ExternalPotential::Harmonic{stiffness, center} => {
+                let offset = position - Vector::new(center[0] as Real, center[1] as Real);
+                energy += stiffness as Real*offset.length_sq()/2.0;
+                force -= offset*stiffness as Real;
+            },
+            ExternalPotential::Periodic{amplitude, wavelength} => {
+                let k = 2.0*PI/wavelength as Real;
+                energy += amplitude as Real*(k*position.x).sin();
+                force.x -= amplitude as Real*k*(k*position.x).cos();
+            }
+        }
+        (energy, force)
+    }
+}
@@ -0,0 +1,72 @@
+/// Saves `contents` under `name`: into the working directory natively,
+/// as a browser download on the web. Returns where the file went.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save(name: &str, _mime: &str, contents: &[u8]) -> Result<String, String>{
+    std::fs::write(name, contents).map_err(|e| e.to_string())?;
+    location(name)
+}
+
+/// Saves `contents` under `name`: into the working directory natively,
+/// as a browser download on the web. Returns where the file went.
+#[cfg(target_arch = "wasm32")]
+pub fn save(name: &str, mime: &str, contents: &[u8]) -> Result<String, String>{
+    use eframe::wasm_bindgen::JsCast as _;
+
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(contents));
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type(mime);
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)
+        .map_err(|e| format!("{e:?}"))?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob).map_err(|e| format!("{e:?}"))?;
+
+    let document = web_sys::window().and_then(|w| w.document()).ok_or("No document")?;
+    let anchor = document.create_element("a").map_err(|e| format!("{e:?}"))?
+        .dyn_into::<web_sys::HtmlAnchorElement>().map_err(|e| format!("{e:?}"))?;
+    anchor.set_href(&url);
+    anchor.set_download(name);
+    anchor.click();
+    let _ = web_sys::Url::revoke_object_url(&url);
+    Ok(format!("downloads/{name}"))
+}
+
+/// Creates file `name` in the working directory, to be written gradually
+#[cfg(not(target_arch = "wasm32"))]
+pub fn create(name: &str) -> Result<std::io::BufWriter<std::fs::File>, String>{
+    std::fs::File::create(name).map(std::io::BufWriter::new).map_err(|e| e.to_string())
+}
+
+/// Where a file or a directory saved in the working directory went
+#[cfg(not(target_arch = "wasm32"))]
+pub fn location(name: &str) -> Result<String, String>{
+    let path = std::fs::canonicalize(name).map_err(|e| e.to_string())?;
+    Ok(path.display().to_string())
+}
+
+/// Numbered `frame_00000.<extension>`, `frame_00001.<extension>`, ... saved one by one into a directory
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Sequence{
+    dir: String,
+    extension: String,
+    saved: usize
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Sequence{
+    /// Creates directory `dir` for the files
+    pub fn new(dir: &str, extension: &str) -> Result<Self, String>{
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        Ok(Self{dir: dir.into(), extension: extension.into(), saved: 0})
+    }
+
+    /// Saves the next file
+    pub fn save(&mut self, contents: &[u8]) -> Result<(), String>{
+        std::fs::write(format!("{}/frame_{:05}.{}", self.dir, self.saved, self.extension), contents).map_err(|e| e.to_string())?;
+        self.saved += 1;
+        Ok(())
+    }
+
+    /// Where the files went
+    pub fn location(&self) -> Result<String, String>{
+        location(&self.dir)
+    }
+}
@@ -0,0 +1,67 @@
+use crate::diagnostics::Diagnostics;
+use crate::observables::Recorder;
+use crate::physics::{Parameters, Simulation};
+use crate::raster::{Image, RecordingOptions};
+use crate::scenario::Scenario;
+
+/// A simulation running without any UI, for scripts and batch jobs
+pub struct Run{
+    pub parameters: Parameters,
+    pub dt: f32,
+    pub simulation: Simulation,
+    pub recorder: Recorder,
+    pub diagnostics: Diagnostics
+}
+
+impl Run{
+    /// Initiates a simulation from `parameters`, averaging observables over `window`.
+    /// Fails if the balls don't fit in the box
+    pub fn new(parameters: Parameters, dt: f32, window: f64) -> Result<Self, String>{
+        let mut simulation = Simulation::new();
+        simulation.random_initiation(&parameters)?;
+        let mut recorder = Recorder::new(window);
+        recorder.clear(&simulation);
+        let diagnostics = Diagnostics::new(&simulation);
+        Ok(Self{parameters, dt, simulation, recorder, diagnostics})
+    }
+
+    /// Initiates a simulation described by `scenario`, recording the observables it asks for
+    pub fn from_scenario(scenario: &Scenario) -> Result<Self, String>{
+        let mut run = Self::new(scenario.parameters.clone(), scenario.dt, scenario.window)?;
+        run.recorder.observables = scenario.observables.clone();
+        Ok(run)
+    }
+
+    pub fn step(&mut self){
+        self.simulation.step(self.dt);
+        self.recorder.observe(&self.simulation, self.dt as f64);
+        self.diagnostics.observe(&self.simulation);
+    }
+
+    /// Runs for `duration` of simulated time
+    pub fn run_for(&mut self, duration: f64){
+        let end = self.simulation.time + duration;
+        // half a step of slack, so rounding of `dt` doesn't add an extra step
+        while self.simulation.time + self.dt as f64*0.5 < end{
+            self.step();
+        }
+    }
+
+    /// Runs on, rendering a frame every `options.time_per_frame` and handing it to `frame`
+    /// before the next one is rendered. Fails if the frames can't be that size
+    pub fn record(&mut self, frames: usize, options: &RecordingOptions, mut frame: impl FnMut(Image)) -> Result<(), String>{
+        for _ in 0..frames{
+            frame(Image::render(&self.simulation.scene(options.style), options.width, options.height)?);
+            self.run_for(options.time_per_frame);
+        }
+        Ok(())
+    }
+
+    pub fn to_csv(&self) -> String{
+        self.recorder.to_csv(&self.parameters, self.dt)
+    }
+
+    pub fn to_json(&self) -> String{
+        self.recorder.to_json(&self.parameters, self.dt)
+    }
+}
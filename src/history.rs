@@ -0,0 +1,69 @@
+use std::collections::VecDeque;
+
+use crate::physics::Simulation;
+
+/// Ring buffer of past simulation states.
+///
+/// Stores full snapshots rather than reversing velocities,
+/// so it works for the irreversible demons as well.
+pub struct History{
+    states: VecDeque<Simulation>,
+    capacity: usize,
+    /// Minimal simulated time between two snapshots
+    pub interval: f64
+}
+
+impl History{
+    pub fn new(capacity: usize, interval: f64) -> Self{
+        Self{states: VecDeque::with_capacity(capacity), capacity, interval}
+    }
+
+    /// Stores a snapshot if at least `interval` of simulated time passed since the last one,
+    /// dropping the oldest snapshot when the buffer is full
+    pub fn record(&mut self, simulation: &Simulation){
+        if let Some(last) = self.states.back() && simulation.time - last.time < self.interval{
+            return
+        }
+        if self.capacity == 0{
+            return
+        }
+        while self.states.len() >= self.capacity{
+            self.states.pop_front();
+        }
+        self.states.push_back(simulation.clone());
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Simulation>{
+        self.states.get(index)
+    }
+
+    pub fn len(&self) -> usize{
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.states.is_empty()
+    }
+
+    /// Forgets every snapshot after `index`, so the timeline can be replayed from there
+    pub fn truncate(&mut self, index: usize){
+        self.states.truncate(index + 1);
+    }
+
+    pub fn clear(&mut self){
+        self.states.clear();
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize){
+        self.capacity = capacity;
+        while self.states.len() > capacity{
+            self.states.pop_front();
+        }
+    }
+}
+
+impl Default for History{
+    fn default() -> Self{
+        Self::new(600, 0.05)
+    }
+}
@@ -1,5 +1,30 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 mod app;
-mod physics;
+mod files;
+mod history;
+pub mod balance;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cli;
+pub mod control;
+pub mod diagnostics;
+pub mod ensemble;
+pub mod field;
+pub mod headless;
+pub mod noise;
+pub mod observables;
+pub mod physics;
+pub mod placement;
+pub mod potential;
+pub mod ratchet;
+pub mod raster;
+pub mod scenario;
+pub mod scene;
+pub mod steady;
+pub mod svg;
+pub mod szilard;
+pub mod tape;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod sweep;
+pub mod vector;
 pub use app::TemplateApp;
@@ -7,6 +7,16 @@ fn main() -> eframe::Result<()> {
     // Log to stdout (if you run with `RUST_LOG=debug`).
     tracing_subscriber::fmt::init();
 
+    // Any arguments mean a headless run from the command line
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        if let Err(e) = maxwells_demon::cli::main(&args) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let native_options = eframe::NativeOptions::default();
     eframe::run_native(
         "Maxwell's demons",
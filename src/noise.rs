@@ -0,0 +1,37 @@
+use rand::RngCore;
+
+/// Random numbers drawn while the simulation runs, e.g. by thermal walls.
+///
+/// SplitMix64, whose whole state is one number: snapshots and the undo history restore it,
+/// so a rewound run repeats the same draws.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Noise{
+    state: u64
+}
+
+impl Noise{
+    pub fn new(seed: u64) -> Self{
+        Self{state: seed}
+    }
+}
+
+impl RngCore for Noise{
+    fn next_u32(&mut self) -> u32{
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64{
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]){
+        for chunk in dest.chunks_mut(8){
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
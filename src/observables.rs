@@ -0,0 +1,281 @@
+use std::fmt::Write;
+
+use crate::physics::{Parameters, Simulation};
+use crate::steady::SteadyState;
+
+/// Observables averaged over one measuring window
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Sample{
+    /// Simulated time at the end of the window
+    pub time: f64,
+    /// Mean number of balls in the left chamber
+    pub left: f64,
+    /// Mean number of balls in the right chamber
+    pub right: f64,
+    /// Mean left chamber density, in percents.
+    /// None if there were no balls for the whole window
+    pub density: Option<f64>,
+    /// Mean mass times squared speed in the left chamber, same units as [`Parameters::temperature`].
+    /// None if the chamber stayed empty for the whole window
+    pub temperature_left: Option<f64>,
+    /// Mean mass times squared speed in the right chamber, same units as [`Parameters::temperature`].
+    /// None if the chamber stayed empty for the whole window
+    pub temperature_right: Option<f64>,
+    /// Net number of balls passing from left to right per unit of time
+    pub flux: f64,
+    /// Turns of the ratchet axle per unit of time, zero without a ratchet
+    #[serde(default)]
+    pub rotation: f64
+}
+
+/// A recorded quantity, i.e. a column of the exports
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Observable{
+    Left,
+    Right,
+    Density,
+    TemperatureLeft,
+    TemperatureRight,
+    Flux,
+    Rotation
+}
+
+impl Observable{
+    pub const ALL: [Observable; 7] = [
+        Observable::Left,
+        Observable::Right,
+        Observable::Density,
+        Observable::TemperatureLeft,
+        Observable::TemperatureRight,
+        Observable::Flux,
+        Observable::Rotation
+    ];
+
+    /// Column name in the exports
+    pub fn name(self) -> &'static str{
+        match self{
+            Observable::Left => "left",
+            Observable::Right => "right",
+            Observable::Density => "density",
+            Observable::TemperatureLeft => "temperature_left",
+            Observable::TemperatureRight => "temperature_right",
+            Observable::Flux => "flux",
+            Observable::Rotation => "rotation",
+        }
+    }
+}
+
+impl Sample{
+    /// Value of `observable`, None if it isn't defined in this window
+    pub fn get(&self, observable: Observable) -> Option<f64>{
+        match observable{
+            Observable::Left => Some(self.left),
+            Observable::Right => Some(self.right),
+            Observable::Density => self.density,
+            Observable::TemperatureLeft => self.temperature_left,
+            Observable::TemperatureRight => self.temperature_right,
+            Observable::Flux => Some(self.flux),
+            Observable::Rotation => Some(self.rotation),
+        }
+    }
+}
+
+/// Collects [`Sample`]s while the simulation runs
+#[derive(Debug, Clone)]
+pub struct Recorder{
+    /// Simulated time every sample is averaged over
+    pub window: f64,
+    /// Columns written by [`Recorder::to_csv`] and [`Recorder::to_json`]
+    pub observables: Vec<Observable>,
+    samples: Vec<Sample>,
+
+    elapsed: f64,
+    steps: u32,
+    left_sum: f64,
+    right_sum: f64,
+    density_sum: f64,
+    /// Steps with some balls, which the density is averaged over
+    density_steps: u32,
+    squared_speed_left: f64,
+    squared_speed_right: f64,
+    crossed_right: u64,
+    crossed_left: u64,
+    turns: f64
+}
+
+/// Layout of the JSON export
+#[derive(serde::Serialize)]
+struct Export<'a>{
+    parameters: &'a Parameters,
+    dt: f32,
+    window: f64,
+    steady_state: serde_json::Map<String, serde_json::Value>,
+    samples: Vec<serde_json::Map<String, serde_json::Value>>
+}
+
+impl Recorder{
+    pub fn new(window: f64) -> Self{
+        Self{
+            window,
+            observables: Observable::ALL.to_vec(),
+            samples: vec![],
+            elapsed: 0.0,
+            steps: 0,
+            left_sum: 0.0,
+            right_sum: 0.0,
+            density_sum: 0.0,
+            density_steps: 0,
+            squared_speed_left: 0.0,
+            squared_speed_right: 0.0,
+            crossed_right: 0,
+            crossed_left: 0,
+            turns: 0.0
+        }
+    }
+
+    pub fn samples(&self) -> &[Sample]{
+        &self.samples
+    }
+
+    /// Accounts a state reached after a step of `dt`, pushing a sample once the window is over
+    pub fn observe(&mut self, simulation: &Simulation, dt: f64){
+        let (left, right) = simulation.structure.count_balls(simulation);
+        let (squared_left, squared_right) = simulation.structure.temperature_sums(simulation);
+        self.steps += 1;
+        self.elapsed += dt;
+        self.left_sum += left as f64;
+        self.right_sum += right as f64;
+        if left + right > 0{
+            self.density_sum += (left as f64)/((left + right) as f64)*100.0;
+            self.density_steps += 1;
+        }
+        self.squared_speed_left += squared_left;
+        self.squared_speed_right += squared_right;
+
+        // half a step of slack, so rounding of `dt` does not stretch the window by a step
+        if self.elapsed + dt*0.5 >= self.window{
+            let steps = self.steps as f64;
+            let temperature = |squared: f64, balls: f64| if balls > 0.0{Some(squared/balls)}else{None};
+            let passed = (simulation.crossed_right as f64 - self.crossed_right as f64)
+                - (simulation.crossed_left as f64 - self.crossed_left as f64);
+            self.samples.push(Sample{
+                time: simulation.time,
+                left: self.left_sum/steps,
+                right: self.right_sum/steps,
+                density: if self.density_steps > 0{Some(self.density_sum/self.density_steps as f64)}else{None},
+                temperature_left: temperature(self.squared_speed_left, self.left_sum),
+                temperature_right: temperature(self.squared_speed_right, self.right_sum),
+                flux: passed/self.elapsed,
+                rotation: (simulation.turns() - self.turns)/self.elapsed
+            });
+            self.restart_window(simulation);
+        }
+    }
+
+    /// Drops the partially collected window, e.g. after the simulation was replaced
+    pub fn restart_window(&mut self, simulation: &Simulation){
+        *self = Self{
+            samples: std::mem::take(&mut self.samples),
+            observables: std::mem::take(&mut self.observables),
+            crossed_right: simulation.crossed_right,
+            crossed_left: simulation.crossed_left,
+            turns: simulation.turns(),
+            ..Self::new(self.window)
+        };
+    }
+
+    /// Forgets everything recorded, starting from `simulation`
+    pub fn clear(&mut self, simulation: &Simulation){
+        self.samples.clear();
+        self.restart_window(simulation);
+    }
+
+    /// Forgets samples recorded after `time`
+    pub fn truncate(&mut self, time: f64){
+        self.samples.retain(|s| s.time <= time);
+    }
+
+    /// Steady state reached by `observable`, if any so far, skipping the samples it isn't defined in
+    pub fn steady_state(&self, observable: Observable) -> Option<SteadyState>{
+        let (times, values): (Vec<f64>, Vec<f64>) = self.samples.iter()
+            .filter_map(|s| Some((s.time, s.get(observable)?)))
+            .unzip();
+        SteadyState::detect(&times, &values)
+    }
+
+    /// Samples as CSV, with the run parameters in `#`-comment lines at the top
+    pub fn to_csv(&self, parameters: &Parameters, dt: f32) -> String{
+        let mut out = String::new();
+        let _ = writeln!(out, "# parameters = {}", serde_json::to_string(parameters).unwrap_or_default());
+        let _ = writeln!(out, "# seed = {}", parameters.seed);
+        let _ = writeln!(out, "# dt = {}", dt);
+        let _ = writeln!(out, "# window = {}", self.window);
+        for &observable in &self.observables{
+            let _ = match self.steady_state(observable){
+                Some(s) => writeln!(out, "# steady {}: relaxation_time = {}, mean = {}, ci95 = {}",
+                    observable.name(), s.relaxation_time, s.mean, s.half_width),
+                None => writeln!(out, "# steady {}: not reached", observable.name())
+            };
+        }
+        out.push_str("time");
+        for observable in &self.observables{
+            let _ = write!(out, ",{}", observable.name());
+        }
+        out.push('\n');
+        for sample in &self.samples{
+            let _ = write!(out, "{}", sample.time);
+            for &observable in &self.observables{
+                // left empty where the observable isn't defined
+                let _ = match sample.get(observable){
+                    Some(value) => write!(out, ",{value}"),
+                    None => write!(out, ",")
+                };
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Samples together with the run parameters as a JSON document
+    pub fn to_json(&self, parameters: &Parameters, dt: f32) -> String{
+        let samples = self.samples.iter().map(|sample| {
+            let mut row = serde_json::Map::new();
+            row.insert("time".into(), sample.time.into());
+            for &observable in &self.observables{
+                row.insert(observable.name().into(), sample.get(observable).into());
+            }
+            row
+        }).collect();
+        let steady_state = self.observables.iter()
+            .map(|&o| (o.name().into(), serde_json::to_value(self.steady_state(o)).unwrap_or_default()))
+            .collect();
+        serde_json::to_string_pretty(&Export{parameters, dt, window: self.window, steady_state, samples})
+            .expect("Samples are always serializable")
+    }
+}
+
+impl Default for Recorder{
+    fn default() -> Self{
+        Self::new(0.3)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn empty_box_has_no_density_or_temperature(){
+        let simulation = Simulation::new();
+        let mut recorder = Recorder::new(0.1);
+        recorder.clear(&simulation);
+        for _ in 0..10{
+            recorder.observe(&simulation, 0.01);
+        }
+        let sample = recorder.samples()[0];
+        assert_eq!((sample.density, sample.temperature_left, sample.temperature_right), (None, None, None));
+        assert!(!recorder.to_csv(&Parameters::default(), 0.01).contains("NaN"));
+        assert!(!recorder.to_json(&Parameters::default(), 0.01).contains("NaN"));
+    }
+}
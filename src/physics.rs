@@ -1,55 +1,361 @@
-use std::f32::consts::PI;
-
-use eframe::emath::{RectTransform, Rot2};
-use egui::{Color32, Painter, Pos2, Rect, Stroke, StrokeKind, Vec2};
-use rand::Rng;
+use eframe::emath::RectTransform;
+use egui::{Painter, Rect, Vec2};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use rand_distr::{StandardNormal};
 
-#[derive(serde::Deserialize, serde::Serialize, Debug)]
+use crate::control::{Control, Controller};
+use crate::field::Field;
+use crate::noise::Noise;
+use crate::placement;
+use crate::potential::{Interaction, Neighbours};
+use crate::ratchet::{Ratchet, RatchetParameters};
+use crate::scene::{Block, Disk, Scene, Style};
+use crate::szilard::{Szilard, SzilardParameters};
+use crate::tape::Tape;
+use crate::vector::{PI, Real, Vector, to_f32, to_f64};
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
 pub struct Ball{
-    coord: Vec2,
-    speed: Vec2,
-    inside_maxwell: bool
+    coord: Vector,
+    speed: Vector,
+    inside_maxwell: bool,
+    /// Index into [`Simulation::species`]
+    #[serde(default)]
+    species: usize
 }
 
-#[derive(serde::Deserialize, serde::Serialize, Debug)]
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
 pub struct BoxStructure{
-    width: f32,
-    height: f32,
-    wall_left: f32,
-    wall_right: f32,
+    width: Real,
+    height: Real,
+    wall_left: Real,
+    wall_right: Real,
     maxwell: Maxwell
 }
 
-#[derive(serde::Deserialize, serde::Serialize, Debug)]
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
 pub struct Simulation{
     pub structure: BoxStructure,
-    pub collision_radius: f32,
+    pub collision_radius: Real,
     pub collisions: bool,
+    /// Simulated time, advanced only by [`Simulation::step`]
+    #[serde(default)]
+    pub time: f64,
+    /// Number of balls that moved from the left chamber to the right one
+    #[serde(default)]
+    pub crossed_right: u64,
+    /// Number of balls that moved from the right chamber to the left one
+    #[serde(default)]
+    pub crossed_left: u64,
+    #[serde(default = "Species::default_list")]
+    pub species: Vec<Species>,
+    /// What the walls gave to the balls since the initiation
+    #[serde(default)]
+    pub by_walls: Exchange,
+    /// What the demon gave to the balls since the initiation
+    #[serde(default)]
+    pub by_demon: Exchange,
+    /// Momentum the external field gave to the balls, its energy is part of [`Simulation::potential_energy`]
+    #[serde(default)]
+    pub by_field: Exchange,
+    #[serde(default)]
+    pub interaction: Interaction,
+    #[serde(default)]
+    pub field: Field,
+    #[serde(default)]
+    pub thermal_walls: ThermalWalls,
+    #[serde(default)]
+    pub ratchet: Option<Ratchet>,
+    #[serde(default)]
+    pub szilard: Option<Szilard>,
+    /// Memory of the [`MaxwellType::Informed`] demon
+    #[serde(default)]
+    pub tape: Option<Tape>,
+    /// Switches the demon in time, if any
+    #[serde(default)]
+    pub control: Option<Controller>,
+    /// Draws of the thermal walls
+    #[serde(default)]
+    noise: Noise,
+    /// Energy of the soft interactions and of the balls in the external field
+    #[serde(default)]
+    potential_energy: f64,
+    /// Soft interaction and external forces on every ball, computed after each step
+    #[serde(skip)]
+    forces: Vec<Vector>,
+    #[serde(skip)]
+    neighbours: Neighbours,
     balls: Vec<Ball>
 }
 
+/// Kinetic energy and momentum given to the balls, or held by them
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Exchange{
+    pub energy: f64,
+    pub momentum: [f64; 2]
+}
+
+impl Exchange{
+    /// Energy and momentum of a ball of `mass` moving with `speed`
+    fn of(mass: Real, speed: Vector) -> Self{
+        let momentum = speed*mass;
+        Self{
+            energy: to_f64(mass*speed.length_sq())/2.0,
+            momentum: [to_f64(momentum.x), to_f64(momentum.y)]
+        }
+    }
+}
+
+impl std::ops::Add for Exchange{
+    type Output = Self;
+    fn add(self, other: Self) -> Self{
+        Self{
+            energy: self.energy + other.energy,
+            momentum: [self.momentum[0] + other.momentum[0], self.momentum[1] + other.momentum[1]]
+        }
+    }
+}
+
+impl std::ops::Sub for Exchange{
+    type Output = Self;
+    fn sub(self, other: Self) -> Self{
+        Self{
+            energy: self.energy - other.energy,
+            momentum: [self.momentum[0] - other.momentum[0], self.momentum[1] - other.momentum[1]]
+        }
+    }
+}
+
+/// Everything needed to reproduce a run of [`Simulation::random_initiation`]
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Parameters{
+    pub temperature: f32,
+    pub radius: f32,
+    pub collisions: bool,
+    pub seed: u64,
+    pub filter_type: MaxwellType,
+    pub geometry: Geometry,
+    pub initial: InitialCondition,
+    pub species: Vec<Species>,
+    pub interaction: Interaction,
+    pub field: Field,
+    pub thermal_walls: ThermalWalls,
+    /// A ratchet and pawl across the partition, if any
+    pub ratchet: Option<RatchetParameters>,
+    /// Runs the single ball as a Szilard engine, if set
+    pub szilard: Option<SzilardParameters>,
+    /// Changes the demon in time, if set
+    pub control: Option<Control>
+}
+
+impl Default for Parameters{
+    fn default() -> Self{
+        Self{
+            temperature: 1.0,
+            radius: 0.009,
+            collisions: true,
+            seed: 0,
+            filter_type: MaxwellType::Tennis,
+            geometry: Geometry::default(),
+            initial: InitialCondition::default(),
+            species: Species::default_list(),
+            interaction: Interaction::HardDisks,
+            field: Field::default(),
+            thermal_walls: ThermalWalls::default(),
+            ratchet: None,
+            szilard: None,
+            control: None
+        }
+    }
+}
+
+impl Parameters{
+    /// Every demon that may act, the ones the [`Control`] selects from if there is one
+    pub fn demons(&self) -> Vec<MaxwellType>{
+        match self.control{
+            Some(control) => control.demons(self.filter_type).to_vec(),
+            None => vec![self.filter_type]
+        }
+    }
+
+    pub fn balls_n(&self) -> usize{
+        self.species.iter().map(|s| s.count as usize).sum()
+    }
+}
+
+/// Sizes of the partition between the chambers and of the demon aperture in it
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct Geometry{
+    pub wall_width: f32,
+    pub filter_height: f32
+}
+
+impl Default for Geometry{
+    fn default() -> Self{
+        Self{wall_width: 0.05, filter_height: 0.8}
+    }
+}
+
+/// Temperatures of the outer walls of each chamber, in the units of [`Parameters::temperature`].
+///
+/// A ball bouncing off a thermal wall leaves it with a speed drawn from the wall temperature,
+/// `None` walls reflect the balls elastically. The partition is never thermal.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(default)]
+pub struct ThermalWalls{
+    pub left: Option<f32>,
+    pub right: Option<f32>
+}
+
+/// A kind of balls. All balls have the same radius, but may differ in mass
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Species{
+    pub name: String,
+    pub count: u16,
+    pub mass: f32,
+    /// Coupling to the electric part of the [`Field`]
+    pub charge: f32
+}
+
+impl Default for Species{
+    fn default() -> Self{
+        Self{name: "gas".into(), count: 60, mass: 1.0, charge: 0.0}
+    }
+}
+
+impl Species{
+    fn default_list() -> Vec<Self>{
+        vec![Self::default()]
+    }
+}
+
+/// How the balls are placed and launched by [`Simulation::random_initiation`]
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq, Default)]
+pub enum InitialCondition{
+    /// Uniformly over both chambers, with Maxwellian velocities
+    #[default]
+    Uniform,
+    /// All balls in the left chamber, or in the right one, for a free expansion
+    OneChamber{left: bool},
+    /// Uniformly over both chambers, with Maxwellian velocities of a temperature for each chamber
+    TwoTemperatures{left: f32, right: f32},
+    /// Uniformly over both chambers, every ball of a species with the same speed in a random direction
+    Monoenergetic,
+    /// On a square lattice filling both chambers, with Maxwellian velocities
+    Lattice,
+    /// Balls of a file, one a line as `x, y, vx, vy[, species]`. The caller reads it with [`InitialCondition::load`],
+    /// the simulation itself doesn't read files
+    File{path: String},
+    /// Positions, velocities and species of every ball. The species counts of [`Parameters`] are ignored
+    Given{balls: Vec<(Vector, Vector, usize)>}
+}
+
+impl InitialCondition{
+    /// Reads the balls of [`InitialCondition::File`], giving them as [`InitialCondition::Given`].
+    /// Other conditions are returned as they are
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(&self) -> Result<Self, String>{
+        match self{
+            InitialCondition::File{path} => {
+                let text = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+                Ok(InitialCondition::Given{balls: Self::parse(&text).map_err(|e| format!("{path}: {e}"))?})
+            },
+            other => Ok(other.clone())
+        }
+    }
+
+    /// Reads the balls of [`InitialCondition::File`] from `text`: their positions, velocities and species
+    pub fn parse(text: &str) -> Result<Vec<(Vector, Vector, usize)>, String>{
+        text.lines().enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+            .map(|(i, line)| {
+                let invalid = || format!("line {}: expected x, y, vx, vy and optionally the species, got \"{line}\"", i + 1);
+                let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+                if !(4..=5).contains(&fields.len()){
+                    return Err(invalid())
+                }
+                let numbers = fields[..4].iter().map(|f| f.parse::<Real>().ok().filter(|x| x.is_finite())).collect::<Option<Vec<_>>>().ok_or_else(invalid)?;
+                let species = match fields.get(4){
+                    Some(f) => f.parse().map_err(|_| invalid())?,
+                    None => 0
+                };
+                Ok((Vector::new(numbers[0], numbers[1]), Vector::new(numbers[2], numbers[3]), species))
+            })
+            .collect()
+    }
+}
+
 #[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq)]
 pub enum MaxwellType{
     Diode,
-    Temperature{t: f32},
+    /// The classic demon: lets a ball moving right through if its energy is above `fast`, one moving left
+    /// if it's below `slow`, and reflects the rest, so the right chamber heats up and the left one cools down
+    Temperature{fast: f32, slow: f32, #[serde(default)] energy: Energy},
     Tennis,
     PhaseConserving {c: f32},
-    Empty
+    Empty,
+    /// Lets balls through like the diode, but writes each measurement to a [`Tape`] of `capacity` bits.
+    /// Erasing the full tape costs `erasure` a bit, paid by the gas if `from_gas`, otherwise by a heat bath
+    Informed {capacity: u32, erasure: f32, from_gas: bool},
+    /// Draws its decision or the outgoing speed at random
+    Stochastic(Kernel),
+    /// Reflects every ball, as if there was no aperture
+    Wall,
+    /// Phase conserving demon whose constant changes linearly from `bottom` to `top` of the aperture.
+    /// Sines pushed past ±1 wrap around to the other end instead of reflecting the ball, so it's reversible
+    Graded {bottom: f32, top: f32},
+    /// Mirror across the aperture, tilted by angles (in radians) changing linearly from `bottom` to `top`.
+    /// Sends every ball back through the face it came in. Glancing balls the tilt would turn across
+    /// the partition are reflected plainly instead, which makes it irreversible for them
+    Mirror {bottom: f32, top: f32}
+}
+
+/// What the [`MaxwellType::Temperature`] demon measures of a ball.
+/// Both are in the units of kinetic energy, whose mean is half the [`Parameters::temperature`]
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Energy{
+    /// The whole kinetic energy
+    #[default]
+    Kinetic,
+    /// Kinetic energy of the motion across the partition, half as large on average
+    Normal
+}
+
+/// Random rule of the [`MaxwellType::Stochastic`] demon
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum Kernel{
+    /// Lets a ball moving right or left through with the given probability, turns it back otherwise
+    Transmission{right: f32, left: f32},
+    /// Gives the ball a new speed, as a wall of `temperature` would, keeping its direction across the partition
+    Thermalizing{temperature: f32}
+}
+
+/// Where a ball hits the demon
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Impact{
+    /// Along the aperture, from 0 at its bottom to 1 at its top
+    pub position: Real,
+    /// Whether the ball enters through the left face
+    pub from_left: bool
 }
 
-#[derive(serde::Deserialize, serde::Serialize, Debug)]
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
 pub struct Maxwell{
     filter_type: MaxwellType,
-    top: f32,
-    bottom: f32
+    top: Real,
+    bottom: Real
 }
 
 impl Maxwell{
     fn new(filter_type: MaxwellType, height: f32) -> Self{
+        let height = height as Real;
         Self{filter_type, top: (1.0 + height)/2.0, bottom: (1.0 - height)/2.0}
     }
-    fn in_bounds(&self, structure: &BoxStructure, coords: Vec2, collision_radius: f32) -> bool{
+    fn in_bounds(&self, structure: &BoxStructure, coords: Vector, collision_radius: Real) -> bool{
         if self.top == self.bottom{
             return false
         }
@@ -58,64 +364,65 @@ impl Maxwell{
         inside_wall && accurate_y
     }
 
-    fn refract_ball(&self, ball: &mut Ball){
+    /// Where the ball at `coord` hits the demon
+    fn impact(&self, structure: &BoxStructure, coord: Vector) -> Impact{
+        Impact{
+            position: ((coord.y - self.bottom)/(self.top - self.bottom)).clamp(0.0, 1.0),
+            from_left: coord.x < (structure.wall_left + structure.wall_right)/2.0
+        }
+    }
+
+    fn refract_ball(&self, ball: &mut Ball, impact: Impact, mass: Real, noise: &mut Noise){
         match self.filter_type {
             MaxwellType::Empty => {},
-            MaxwellType::Diode => {
+            MaxwellType::Wall => ball.speed.x = -ball.speed.x,
+            MaxwellType::Diode | MaxwellType::Informed{..} => {
                 if ball.speed.x < 0.0{
                     ball.speed.x = - ball.speed.x;
                 }
             },
-            MaxwellType::Temperature { t } => {
-                if ball.speed.x < 0.0{
-                    if ball.speed.x.powf(2.0) < 1.0{
-                        ball.speed.x = - ball.speed.x;
-                    }
-                }
-                else if ball.speed.x.powf(2.0) > t{
-                    ball.speed.x = - ball.speed.x;
-                }
-            },  
-            MaxwellType::Tennis => {
-             //   print!("{:?}", ball.speed);
-                if ball.speed.x * ball.speed.y >= 0.0 && ball.speed.x < ball.speed.y{
-                    (ball.speed.x, ball.speed.y) = (ball.speed.y, ball.speed.x)
-                }
-                else if (ball.speed.x  < 0.0 &&  ball.speed.y > 0.0 && ball.speed.x.abs() > ball.speed.y.abs() ) 
-                    || (ball.speed.x  > 0.0 &&  ball.speed.y < 0.0 && ball.speed.x.abs() < ball.speed.y.abs() ){
-                    (ball.speed.x, ball.speed.y) = (-ball.speed.y, -ball.speed.x)
-                } 
-                else{
+            MaxwellType::Temperature { fast, slow, energy } => {
+                let measured = match energy{
+                    Energy::Kinetic => mass*ball.speed.length_sq()/2.0,
+                    Energy::Normal => mass*ball.speed.x*ball.speed.x/2.0
+                };
+                let passes = if ball.speed.x > 0.0{measured > fast as Real}else{measured < slow as Real};
+                if !passes{
                     ball.speed.x = -ball.speed.x;
                 }
-               // println!(" → {:?}", ball.speed)
             },
-            MaxwellType::PhaseConserving { c } => {
-                let speed = ball.speed.length();
-                let angle = ball.speed.angle();
-                let new_v = angle.sin() + c;
-                if new_v.abs() <= 1.0{
-                    let new_angle = if angle.abs() < PI/2.0{
-                        new_v.asin()
-                    }
-                    else{
-                        PI - new_v.asin()
-                    };
-                    
-                    ball.speed = Vec2::new(speed*new_angle.cos(), speed*new_angle.sin())
-                }
-                else{
+            MaxwellType::Tennis => ball.speed = tennis(ball.speed),
+            MaxwellType::PhaseConserving { c } => ball.speed = phase_shift(ball.speed, c as Real),
+            MaxwellType::Stochastic(Kernel::Transmission { right, left }) => {
+                let probability = if ball.speed.x > 0.0{right}else{left};
+                if noise.random::<f32>() >= probability{
                     ball.speed.x = -ball.speed.x;
                 }
-                
+            },
+            MaxwellType::Stochastic(Kernel::Thermalizing { temperature }) => {
+                let normal = Vector::new(if ball.speed.x > 0.0{1.0}else{-1.0}, 0.0);
+                ball.speed = thermal_speed(normal, temperature as Real/mass, noise);
+            },
+            MaxwellType::Graded { bottom, top } => if ball.speed.length() > 0.0{
+                let c = bottom as Real + (top - bottom) as Real*impact.position;
+                let sine = (ball.speed.y/ball.speed.length() + c + 1.0).rem_euclid(2.0) - 1.0;
+                let across = (1.0 - sine*sine).max(0.0).sqrt();
+                ball.speed = Vector::new(across.copysign(ball.speed.x), sine)*ball.speed.length();
+            },
+            MaxwellType::Mirror { bottom, top } => {
+                let tilt = bottom as Real + (top - bottom) as Real*impact.position;
+                let normal = Vector::from_polar(1.0, tilt);
+                let reflected = ball.speed - normal*(2.0*ball.speed.dot(normal));
+                // a ball the mirror would turn across the partition hits the edge of the aperture instead
+                ball.speed = if (reflected.x < 0.0) == impact.from_left{reflected}else{Vector::new(-ball.speed.x, ball.speed.y)};
             },
         }
         
     }
 
-    fn coords(&self, structure: &BoxStructure) -> (Pos2, Pos2){
-        (Pos2::new(structure.wall_left, self.bottom),
-        Pos2::new(structure.wall_right, self.top))
+    fn coords(&self, structure: &BoxStructure) -> (Vector, Vector){
+        (Vector::new(structure.wall_left, self.bottom),
+        Vector::new(structure.wall_right, self.top))
     }
 }
 
@@ -124,7 +431,7 @@ impl BoxStructure{
         Self { width: 1.0, height: 1.0, wall_left: 0.48, wall_right: 0.52, maxwell: Maxwell::new(MaxwellType::Tennis, 0.0)}
     }
 
-    fn in_bounds(&self, coords: Vec2, collision_radius: f32) -> bool{
+    fn in_bounds(&self, coords: Vector, collision_radius: Real) -> bool{
         let out_of_box = coords.x > self.width - collision_radius
             ||  coords.y > self.height - collision_radius
             ||  coords.x < collision_radius
@@ -139,31 +446,291 @@ impl BoxStructure{
         (n_left, balls.len() - n_left)
     }
 
-    fn coords(&self) -> (Pos2, Pos2){
-        (Pos2::new(self.wall_left, 0.0),
-        Pos2::new(self.wall_right, self.height))
+    /// Sums of mass times squared speed in the left and right chambers.
+    /// Divided by [`BoxStructure::count_balls`] they give the chamber temperatures
+    /// in the units of [`Parameters::temperature`]
+    pub fn temperature_sums(&self, s: &Simulation) -> (f64, f64){
+        s.balls.iter().fold((0.0, 0.0), |(left, right), b| {
+            let v2 = to_f64(s.mass(b)*b.speed.length_sq());
+            if b.coord.x < self.width*0.5{
+                (left + v2, right)
+            }
+            else{
+                (left, right + v2)
+            }
+        })
+    }
+
+    /// Normal pointing into the box of the outer wall `coords` lie beyond, if any
+    fn outer_normal(&self, coords: Vector, collision_radius: Real) -> Option<Vector>{
+        if coords.x < collision_radius{
+            Some(Vector::new(1.0, 0.0))
+        }
+        else if coords.x > self.width - collision_radius{
+            Some(Vector::new(-1.0, 0.0))
+        }
+        else if coords.y < collision_radius{
+            Some(Vector::new(0.0, 1.0))
+        }
+        else if coords.y > self.height - collision_radius{
+            Some(Vector::new(0.0, -1.0))
+        }
+        else{
+            None
+        }
+    }
+
+    fn coords(&self) -> (Vector, Vector){
+        (Vector::new(self.wall_left, 0.0),
+        Vector::new(self.wall_right, self.height))
     }
 }
 
-macro_rules! inv{
-    ($c:expr, $mode:expr) => {if($mode){$c}else{Simulation::invert_color($c)}}
+impl Default for BoxStructure{
+    fn default() -> Self{
+        Self::new()
+    }
 }
 
+/// Fraction of the ball diameter a ball may move in a substep of soft interactions
+const SUBSTEP_MOVE: Real = 0.005;
+const MAX_SUBSTEPS: Real = 1000.0;
+
 impl Simulation{
     pub fn new() -> Self{
-        Simulation{structure: BoxStructure::new(), collision_radius: 0.1, balls: vec![], collisions: true}
+        Simulation{structure: BoxStructure::new(), collision_radius: 0.1, balls: vec![], collisions: true, time: 0.0, crossed_right: 0, crossed_left: 0, species: Species::default_list(), by_walls: Exchange::default(), by_demon: Exchange::default(), by_field: Exchange::default(),
+            interaction: Interaction::HardDisks, field: Field::default(), thermal_walls: ThermalWalls::default(), ratchet: None, szilard: None, tape: None, control: None, noise: Noise::default(), potential_energy: 0.0, forces: vec![], neighbours: Neighbours::default()}
+    }
+
+    fn mass(&self, ball: &Ball) -> Real{
+        self.species[ball.species].mass as Real
+    }
+
+    /// Energy, kinetic and of the interactions, and momentum of all the balls.
+    /// The energy includes the one of the ratchet
+    pub fn total(&self) -> Exchange{
+        let kinetic = self.balls.iter().fold(Exchange::default(), |sum, b| sum + Exchange::of(self.mass(b), b.speed));
+        let ratchet = self.ratchet.as_ref().map_or(0.0, Ratchet::energy);
+        Exchange{energy: kinetic.energy + self.potential_energy + ratchet, ..kinetic}
+    }
+
+    /// Mass of all the balls
+    pub fn total_mass(&self) -> f64{
+        self.balls.iter().map(|b| to_f64(self.mass(b))).sum()
+    }
+
+    /// Turns of the ratchet axle, zero without a ratchet
+    pub fn turns(&self) -> f64{
+        self.ratchet.as_ref().map_or(0.0, Ratchet::turns)
+    }
+
+    /// Energy of the soft interactions and of the balls in the external field
+    pub fn potential_energy(&self) -> f64{
+        self.potential_energy
+    }
+
+    fn soft(&self) -> bool{
+        self.collisions && self.interaction.is_soft()
     }
 
-    pub fn step(&mut self, t: f32){
-        if self.collisions {
-            self.ball_collider(t);
+    /// Whether the balls move under forces rather than in straight lines
+    fn forced(&self) -> bool{
+        self.soft() || self.field.is_active()
+    }
+
+    /// Recomputes the forces and the potential energy for the current positions
+    fn update_forces(&mut self){
+        self.forces = vec![Vector::ZERO; self.balls.len()];
+        self.potential_energy = 0.0;
+        for (ball, force) in self.balls.iter().zip(&mut self.forces){
+            let species = &self.species[ball.species];
+            let (energy, external) = self.field.at(ball.coord, self.structure.height, species.mass as Real, species.charge as Real);
+            self.potential_energy += to_f64(energy);
+            *force = external;
+        }
+
+        let sigma = 2.0*self.collision_radius;
+        if !self.soft() || sigma <= 0.0{
+            return
+        }
+        let positions: Vec<Vector> = self.balls.iter().map(|b| b.coord).collect();
+        let size = Vector::new(self.structure.width, self.structure.height);
+        self.neighbours.update(&positions, self.interaction.cutoff(sigma), size);
+        for &(i, j) in self.neighbours.pairs(){
+            let delta = positions[i] - positions[j];
+            let (energy, force) = self.interaction.pair(delta.length(), sigma);
+            self.potential_energy += to_f64(energy);
+            self.forces[i] += delta*force;
+            self.forces[j] -= delta*force;
+        }
+    }
+
+    /// Velocity half-step of velocity Verlet
+    fn kick(&mut self, t: Real){
+        for (ball, &force) in self.balls.iter_mut().zip(&self.forces){
+            ball.speed += force*(t/self.species[ball.species].mass as Real);
+            // the interactions cancel out in the sum, leaving the impulse of the field
+            self.by_field.momentum[0] += to_f64(force.x*t);
+            self.by_field.momentum[1] += to_f64(force.y*t);
+        }
+    }
+
+    pub fn step(&mut self, dt: f32){
+        let t = dt as Real;
+        self.steer();
+        if self.forced(){
+            if self.forces.len() != self.balls.len(){
+                self.update_forces();
+            }
+            let substeps = if self.soft(){
+                // soft potentials are steep, so no ball moves more than a fraction of its size per substep
+                let fastest = self.balls.iter().map(|b| b.speed.length_sq()).fold(0.0, Real::max).sqrt();
+                // point balls get the most substeps rather than 0/0
+                let size = (SUBSTEP_MOVE*2.0*self.collision_radius).max(Real::EPSILON);
+                let substeps = (t*fastest/size).ceil();
+                // a ball with a NaN speed would give no substeps at all
+                if substeps.is_finite(){substeps.clamp(1.0, MAX_SUBSTEPS)}else{MAX_SUBSTEPS}
+            }
+            else{
+                1.0
+            };
+            let h = t/substeps;
+            for _ in 0..substeps as u32{
+                self.kick(h/2.0);
+                if self.collisions && !self.soft(){
+                    self.ball_collider(h);
+                }
+                let entered = self.move_balls(h);
+                self.turn_ratchet(h);
+                self.run_szilard(h);
+                self.update_forces();
+                self.kick(h/2.0);
+                // after both kicks, so the demon doesn't change the speed the second kick was meant for
+                self.refract(&entered);
+            }
+        }
+        else{
+            if self.collisions {
+                self.ball_collider(t);
+            }
+            let entered = self.move_balls(t);
+            self.turn_ratchet(t);
+            self.run_szilard(t);
+            self.refract(&entered);
+        }
+        self.time += dt as f64;
+    }
+
+    /// Moves the balls in straight lines, bouncing them off the walls.
+    /// Returns the balls which entered the demon, to be passed to [`Simulation::refract`]
+    fn move_balls(&mut self, t: Real) -> Vec<usize>{
+        let forced = self.forced();
+        let middle = self.structure.width*0.5;
+        let mut entered = vec![];
+        for (i, ball) in self.balls.iter_mut().enumerate(){
+            let was_left = ball.coord.x < middle;
+            let mass = self.species[ball.species].mass as Real;
+            let before = Exchange::of(mass, ball.speed);
+            let aim = ball.coord + ball.speed*t;
+            if ball.step(&self.structure, t, self.collision_radius, forced){
+                entered.push(i);
+            }
+            let wall = if was_left{self.thermal_walls.left}else{self.thermal_walls.right};
+            // a ball which bounced off an outer wall now moves away from it
+            if let Some(temperature) = wall
+                && let Some(normal) = self.structure.outer_normal(aim, self.collision_radius)
+                && ball.speed.dot(normal) > 0.0{
+                ball.speed = thermal_speed(normal, temperature as Real/mass, &mut self.noise);
+            }
+            self.by_walls = self.by_walls + (Exchange::of(mass, ball.speed) - before);
+            match (was_left, ball.coord.x < middle){
+                (true, false) => self.crossed_right += 1,
+                (false, true) => self.crossed_left += 1,
+                _ => {}
+            }
+        }
+        entered
+    }
+
+    /// Turns the ratchet for `t` and bounces the balls off it
+    fn turn_ratchet(&mut self, t: Real){
+        let Some(ratchet) = &mut self.ratchet else{
+            return
+        };
+        self.by_walls.energy -= ratchet.advance(t);
+        for ball in &mut self.balls{
+            let mass = self.species[ball.species].mass as Real;
+            let impulse = ratchet.collide(ball.coord, &mut ball.speed, mass, self.collision_radius);
+            self.by_walls.momentum[0] += to_f64(impulse.x);
+            self.by_walls.momentum[1] += to_f64(impulse.y);
+        }
+    }
+
+    /// Moves the Szilard engine on by `t` and bounces the balls off its piston
+    fn run_szilard(&mut self, t: Real){
+        let Some(engine) = &mut self.szilard else{
+            return
+        };
+        if let Some(ball) = self.balls.first(){
+            engine.advance(self.time, t, ball.coord, self.collision_radius, self.structure.width);
         }
         for ball in &mut self.balls{
-            ball.step(&self.structure, t, self.collision_radius);
+            let mass = self.species[ball.species].mass as Real;
+            let before = Exchange::of(mass, ball.speed);
+            engine.collide(&mut ball.coord, &mut ball.speed, mass, self.collision_radius);
+            // the piston is a moving wall
+            self.by_walls = self.by_walls + (Exchange::of(mass, ball.speed) - before);
+        }
+    }
+
+    /// Lets the controller choose the demon for the next step
+    fn steer(&mut self){
+        if self.control.is_none(){
+            return
+        }
+        let (left, right) = self.structure.count_balls(self);
+        if let Some(controller) = &mut self.control{
+            self.structure.maxwell.filter_type = controller.update(self.time, left, right);
+        }
+    }
+
+    /// Lets the demon act on the balls which entered it
+    fn refract(&mut self, entered: &[usize]){
+        for &i in entered{
+            let mass = self.mass(&self.balls[i]);
+            let before = Exchange::of(mass, self.balls[i].speed);
+            let impact = self.structure.maxwell.impact(&self.structure, self.balls[i].coord);
+            // the measurement, whether the ball moves right and is let through, is written before acting on it.
+            // A controller may have switched the informed demon off, then nothing is measured
+            let moving_right = self.balls[i].speed.x > 0.0;
+            let informed = matches!(self.structure.maxwell.filter_type, MaxwellType::Informed{..});
+            let owed = self.tape.as_mut().filter(|_| informed).and_then(|tape| tape.write(moving_right));
+            self.structure.maxwell.refract_ball(&mut self.balls[i], impact, mass, &mut self.noise);
+            self.by_demon = self.by_demon + (Exchange::of(mass, self.balls[i].speed) - before);
+            if let Some(owed) = owed{
+                self.drain(owed);
+            }
         }
     }
 
-    pub fn ball_collider(&mut self, t: f32){
+    /// Takes `energy` out of the kinetic energy of the gas, slowing all balls down alike
+    fn drain(&mut self, energy: f64){
+        let before = self.balls.iter().fold(Exchange::default(), |sum, b| sum + Exchange::of(self.mass(b), b.speed));
+        // the gas can't pay more than it has
+        let paid = energy.min(before.energy);
+        let factor = if before.energy > 0.0{(1.0 - paid/before.energy).sqrt() as Real}else{0.0};
+        for ball in &mut self.balls{
+            ball.speed = ball.speed*factor;
+        }
+        let after = self.balls.iter().fold(Exchange::default(), |sum, b| sum + Exchange::of(self.mass(b), b.speed));
+        self.by_demon = self.by_demon + (after - before);
+        if let Some(tape) = &mut self.tape{
+            tape.paid_by_gas += paid;
+            tape.paid_by_bath += energy - paid;
+        }
+    }
+
+    pub fn ball_collider(&mut self, t: Real){
         for i in 0..self.balls.len(){
             for j in 0..i{
                 let ball = &self.balls[i];
@@ -174,11 +741,13 @@ impl Simulation{
                     let delta = new_coord_one - new_coord_other;
                     //let delta = ball.coord - other_ball.coord;
                     if delta.length() <= 2.0*self.collision_radius{
-                        let cm = (other_ball.speed + ball.speed)/2.0;
+                        // rotating both speeds around the center of mass one conserves momentum and energy
+                        let (mass, other_mass) = (self.mass(ball), self.mass(other_ball));
+                        let cm = (ball.speed*mass + other_ball.speed*other_mass)/(mass + other_mass);
 
                         let angle = delta.angle();
-                        let new_ball_speed = cm - Rot2::from_angle(angle) * (other_ball.speed - cm);
-                        let new_other_speed = cm - Rot2::from_angle(angle) * (ball.speed - cm);
+                        let new_ball_speed = cm + (ball.speed - cm).rotated(angle);
+                        let new_other_speed = cm + (other_ball.speed - cm).rotated(angle);
                         self.balls[i].speed = new_ball_speed;
                         self.balls[j].speed = new_other_speed;
                     }
@@ -187,80 +756,255 @@ impl Simulation{
         }
     }
 
-    pub fn random_initiation(&mut self, balls_n: u16, temperature: f32, radius: f32, filter_height: f32, filter_type: MaxwellType, collisions: bool, wall_width: f32){
-        assert!(temperature >= 0.0);
-        self.balls = Vec::with_capacity(balls_n.into());
-        self.collision_radius = radius;
-        self.structure.maxwell = Maxwell::new(filter_type, filter_height);
-        self.structure.wall_left = 0.5 - wall_width/2.0;
-        self.structure.wall_right = 0.5 + wall_width/2.0;
-        self.collisions = collisions;
-        let mut rng = rand::rng();
+    /// Sets the simulation up as `parameters` say. Fails if the balls don't fit in the box
+    pub fn random_initiation(&mut self, parameters: &Parameters) -> Result<(), String>{
+        // set up apart, so a failure leaves the simulation as it was
+        let mut simulation = Simulation::new();
+        simulation.set_up(parameters)?;
+        *self = simulation;
+        Ok(())
+    }
+
+    /// [`Simulation::random_initiation`] of a new simulation
+    fn set_up(&mut self, parameters: &Parameters) -> Result<(), String>{
+        if parameters.temperature.is_nan() || parameters.temperature < 0.0{
+            return Err(format!("temperature can't be negative, got {}", parameters.temperature))
+        }
+        self.balls = Vec::with_capacity(parameters.balls_n());
+        self.species = parameters.species.clone();
+        self.collision_radius = parameters.radius as Real;
+        self.structure.maxwell = Maxwell::new(parameters.filter_type, parameters.geometry.filter_height);
+        self.structure.wall_left = 0.5 - parameters.geometry.wall_width as Real/2.0;
+        self.structure.wall_right = 0.5 + parameters.geometry.wall_width as Real/2.0;
+        self.collisions = parameters.collisions;
+        self.time = 0.0;
+        self.crossed_right = 0;
+        self.crossed_left = 0;
+        self.by_walls = Exchange::default();
+        self.by_demon = Exchange::default();
+        self.by_field = Exchange::default();
+        self.interaction = parameters.interaction;
+        self.field = parameters.field;
+        self.thermal_walls = parameters.thermal_walls;
+        let middle = Vector::new(0.0, self.structure.height/2.0);
+        self.ratchet = parameters.ratchet.map(|r| Ratchet::new(r,
+            middle + Vector::new(self.structure.wall_left/2.0, 0.0),
+            middle + Vector::new((self.structure.wall_right + self.structure.width)/2.0, 0.0)));
+        let bath = parameters.thermal_walls.left.or(parameters.thermal_walls.right).unwrap_or(parameters.temperature);
+        self.szilard = parameters.szilard.map(|s| Szilard::new(s, bath as Real));
+        // a controller may switch to an informed demon later, it starts with an empty tape then
+        self.tape = parameters.demons().into_iter().find_map(|demon| match demon{
+            MaxwellType::Informed{capacity, erasure, from_gas} =>
+                Some(Tape::new(capacity, erasure as f64, from_gas, parameters.temperature as f64/2.0)),
+            _ => None
+        });
+        self.control = parameters.control.map(|c| Controller::new(c, parameters.filter_type, parameters.temperature as f64/2.0));
+        if let Some(controller) = &self.control{
+            self.structure.maxwell.filter_type = controller.active;
+        }
+        self.potential_energy = 0.0;
+        self.forces.clear();
+        self.neighbours = Neighbours::default();
+        let mut rng = StdRng::seed_from_u64(parameters.seed);
 
-        for _ in 0..balls_n{
-            self.balls.push(Ball::random_initiation(&self.structure, temperature, &mut rng, radius))
+        let structure = &self.structure;
+        let (r, middle) = (self.collision_radius, structure.width*0.5);
+        let free = |coord: Vector| !structure.in_bounds(coord, r);
+        let coords = match parameters.initial{
+            InitialCondition::OneChamber{left} =>
+                placement::place(parameters.balls_n(), structure.width, structure.height, r, |c| free(c) && (c.x < middle) == left, &mut rng)?,
+            InitialCondition::Lattice => placement::regular(parameters.balls_n(), structure.width, structure.height, r, free)?,
+            InitialCondition::File{ref path} =>
+                return Err(format!("the balls of {path} weren't loaded, files are read by the native app and the command line only")),
+            InitialCondition::Given{..} => vec![],
+            _ => placement::place(parameters.balls_n(), structure.width, structure.height, r, free, &mut rng)?
+        };
+        let mut coords = coords.into_iter();
+        for (i, species) in parameters.species.iter().enumerate(){
+            for coord in coords.by_ref().take(species.count as usize){
+                let temperature = match parameters.initial{
+                    InitialCondition::TwoTemperatures{left, right} => if coord.x < middle{left}else{right},
+                    _ => parameters.temperature
+                } as Real/species.mass as Real;
+                let speed = match parameters.initial{
+                    InitialCondition::Monoenergetic => Vector::from_polar(temperature.sqrt(), 2.0*PI*rng.random::<Real>()),
+                    _ => maxwellian(temperature, &mut rng)
+                };
+                self.balls.push(Ball{coord, speed, inside_maxwell: false, species: i})
+            }
+        }
+        if let InitialCondition::Given{balls} = &parameters.initial{
+            for (n, &(coord, speed, species)) in balls.iter().enumerate(){
+                let ball = format!("ball {} at ({}, {})", n + 1, coord.x, coord.y);
+                if species >= self.species.len(){
+                    return Err(format!("{ball}: no species {species}, there are {}", self.species.len()))
+                }
+                if self.structure.in_bounds(coord, r) || self.balls.iter().any(|b| (b.coord - coord).length() < 2.0*r){
+                    return Err(format!("{ball} overlaps a wall or another ball"))
+                }
+                self.balls.push(Ball{coord, speed, inside_maxwell: false, species});
+            }
+        }
+        // the engine measures and moves the first ball only, the bits would mean nothing with others around
+        if self.szilard.is_some() && self.balls.len() != 1{
+            return Err(format!("the Szilard engine runs a single ball, got {}", self.balls.len()))
         }
-        /*self.balls.push(Ball{ coord: Vec2 { x: 0.3, y: 0.3 }, speed: Vec2 { x: 0.1, y: 1.0 }, inside_maxwell: false });
-        self.collision_radius = 0.05;
-        self.structure.maxwell = Maxwell::new(MaxwellType::Tennis, 0.8)*/
+        if self.forced(){
+            self.update_forces();
+        }
+        self.noise = Noise::new(rng.random());
+        Ok(())
+    }
 
+    pub fn paint(&self, painter: &Painter, transform: RectTransform, style: Style) {
+        self.scene(style).paint(painter, transform);
     }
 
-    pub fn paint(&self, painter: &Painter, transform: RectTransform, dark_mode: bool) {
-        
-        let real_radius = transform.scale().x * self.collision_radius;
+    /// What [`Simulation::paint`] draws, for painting elsewhere
+    pub fn scene(&self, style: Style) -> Scene{
         let (p1, p2) = self.structure.coords();
-        painter.rect(Rect::from_two_pos(transform*p1, transform*p2), 1.0, Color32::from_gray(inv!(48, dark_mode)), Stroke::new(1.0, Color32::from_gray(inv!(64, dark_mode))), StrokeKind::Middle);
+        let mut blocks = vec![Block{rect: Rect::from_two_pos(p1.to_pos2(), p2.to_pos2()), fill: style.gray(48), stroke: style.gray(64)}];
         if self.structure.maxwell.top != self.structure.maxwell.bottom{
             let (p1, p2) = self.structure.maxwell.coords(&self.structure);
-            painter.rect(Rect::from_two_pos(transform*p1, transform*p2), 1.0, Color32::from_gray(inv!(16, dark_mode)), Stroke::new(1.0, Color32::from_gray(inv!(16, dark_mode))), StrokeKind::Middle);
+            blocks.push(Block{rect: Rect::from_two_pos(p1.to_pos2(), p2.to_pos2()), fill: style.gray(16), stroke: style.gray(16)});
         }
-        for b in &self.balls{
-            let point = transform * b.coord.to_pos2();
-            painter.circle(point, real_radius, Color32::from_gray(inv!(128, dark_mode)), Stroke::new(1.0, Color32::from_gray(inv!(64, dark_mode))));
+
+        let mean_square = self.balls.iter().map(|b| b.speed.length_sq()).sum::<Real>()/self.balls.len().max(1) as Real;
+        let rms = mean_square.sqrt().max(Real::EPSILON);
+        let middle = self.structure.width*0.5;
+        let disks = self.balls.iter().map(|b| Disk{
+            center: b.coord.to_pos2(),
+            radius: to_f32(self.collision_radius),
+            velocity: b.speed.to_vec2(),
+            fill: style.ball_fill(to_f32(b.speed.length()/rms), b.coord.x < middle, b.species),
+            stroke: style.gray(64)
+        }).collect();
+
+        Scene{
+            size: Vec2::new(to_f32(self.structure.width), to_f32(self.structure.height)),
+            background: style.gray(27),
+            border: style.gray(16),
+            blocks,
+            segments: self.ratchet.iter().flat_map(|r| r.segments(style))
+                .chain(self.szilard.iter().flat_map(|s| s.segments(self.structure.height, style)))
+                .collect(),
+            disks
         }
     }
+}
+
+/// Speed a ball of `mass` hitting the demon at `impact` with `speed` leaves it with
+pub(crate) fn transfer(filter_type: MaxwellType, impact: Impact, speed: Vector, mass: Real, noise: &mut Noise) -> Vector{
+    let mut ball = Ball{coord: Vector::ZERO, speed, inside_maxwell: true, species: 0};
+    Maxwell::new(filter_type, 1.0).refract_ball(&mut ball, impact, mass, noise);
+    ball.speed
+}
+
+/// The tennis demon of Skordos (1992). A ball coming from the left passes if it's grazing, moving faster along
+/// the partition than across it, and one coming from the right passes if it's steep. A passing ball swaps
+/// its speeds across and along the partition, keeping both directions; the rest are reflected.
+///
+/// On the velocities moving across the partition it's a bijection keeping the speed, and it's reversible,
+/// but it turns grazing balls steep and so doesn't keep the phase-space volume. Head-on balls from the right
+/// are reflected, as passing they would leave along the partition
+fn tennis(speed: Vector) -> Vector{
+    let (across, along) = (speed.x.abs(), speed.y.abs());
+    let passes = if speed.x > 0.0{along > across}else{along > 0.0 && along < across};
+    if passes{
+        Vector::new(along.copysign(speed.x), across.copysign(speed.y))
+    }
+    else{
+        Vector::new(-speed.x, speed.y)
+    }
+}
+
+/// Adds `c` to the sine of the angle of `speed`, keeping the ball's direction across the partition.
+/// Reflects the ball if there's no such angle
+fn phase_shift(speed: Vector, c: Real) -> Vector{
+    let angle = speed.angle();
+    let new_v = angle.sin() + c;
+    if new_v.abs() > 1.0{
+        return Vector::new(-speed.x, speed.y)
+    }
+    let new_angle = if angle.abs() < PI/2.0{
+        new_v.asin()
+    }
+    else{
+        PI - new_v.asin()
+    };
+    Vector::from_polar(speed.length(), new_angle)
+}
+
+/// Velocity drawn from the Maxwell distribution at `temperature` divided by the ball mass, in any direction
+fn maxwellian<T: Rng>(temperature: Real, rng: &mut T) -> Vector{
+    // `temperature` is the mean mass times squared speed, split over the two directions
+    let sigma = (temperature/2.0).sqrt();
+    Vector::new(rng.sample::<Real, StandardNormal>(StandardNormal), rng.sample::<Real, StandardNormal>(StandardNormal))*sigma
+}
 
-    fn invert_color(c: u8) -> u8{
-        c.overflowing_neg().0
+/// Speed of a ball leaving a wall of `temperature` (divided by the ball mass) which points into the box along `normal`.
+/// The normal part is drawn from the flux through the wall, so the balls end up in equilibrium with it
+pub(crate) fn thermal_speed(normal: Vector, temperature: Real, noise: &mut Noise) -> Vector{
+    // `temperature` is the mean mass times squared speed, split over the two directions
+    let sigma = (temperature/2.0).sqrt();
+    let across = sigma*(-2.0*(1.0 - noise.random::<Real>()).ln()).sqrt();
+    let along = sigma*noise.sample::<Real, StandardNormal>(StandardNormal);
+    normal*across + Vector::new(-normal.y, normal.x)*along
+}
+
+impl Default for Simulation{
+    fn default() -> Self{
+        Self::new()
     }
 }
 
 impl Ball
 {
-    fn step(&mut self, b: &BoxStructure, t: f32, collision_radius: f32){ // works for any rectangle-based box
+    /// Returns whether the ball entered the demon
+    fn step(&mut self, b: &BoxStructure, t: Real, collision_radius: Real, slide: bool) -> bool{ // works for any rectangle-based box
         let new_coord = self.coord + t*self.speed;
 
         match (self.inside_maxwell, b.maxwell.in_bounds(b, new_coord, collision_radius)){
             (true, true) => {
                 self.coord = new_coord;
+                false
             },
             (false, false) => {
-                self.wall_reflaction(b, new_coord, collision_radius);
+                self.wall_reflaction(b, new_coord, collision_radius, slide);
+                false
             } 
             (true, false) => {
-                if self.wall_reflaction(b, new_coord, collision_radius){
-                    self.inside_maxwell = true;
-                }
-                else{
-                    self.inside_maxwell = false;
-                }
+                self.inside_maxwell = self.wall_reflaction(b, new_coord, collision_radius, slide);
+                false
             },
             (false, true) => {
                 self.inside_maxwell = true;
                 self.coord = new_coord;
-                b.maxwell.refract_ball(self);
+                true
             }
         }
-        
     }
 
-    fn wall_reflaction(&mut self, b: &BoxStructure, new_coord: Vec2, collision_radius: f32) -> bool{
-        if b.in_bounds(Vec2{x: new_coord.x, y: self.coord.y}, collision_radius){ // problem with x
+    /// With `slide` the ball still moves along the wall it bounced off,
+    /// so forces acting during the step don't change its energy without moving it
+    fn wall_reflaction(&mut self, b: &BoxStructure, new_coord: Vector, collision_radius: Real, slide: bool) -> bool{
+        if b.in_bounds(Vector{x: new_coord.x, y: self.coord.y}, collision_radius){ // problem with x
             self.speed.x = -self.speed.x;
+            if slide{
+                if b.in_bounds(Vector{x: self.coord.x, y: new_coord.y}, collision_radius){
+                    // a corner, bouncing back
+                    self.speed.y = -self.speed.y;
+                }
+                else{
+                    self.coord.y = new_coord.y;
+                }
+            }
         }
         else if b.in_bounds(new_coord, collision_radius){ // problem with y
             self.speed.y = -self.speed.y;
+            if slide{
+                self.coord.x = new_coord.x;
+            }
         }
         else{
             self.coord = new_coord;
@@ -268,28 +1012,7 @@ impl Ball
         }
         true
     }
+}
 
-    fn random_initiation<T: Rng>(structure: &BoxStructure, temperature: f32, rng: &mut T, collision_radius: f32) -> Self{
-        let mut attempts = 0;
-        let mut x;
-        let mut y;
-        loop {
-            x = rng.random::<f32>() * structure.width;
-            y = rng.random::<f32>() * structure.height;
-
-            if !structure.in_bounds(Vec2{x, y}, collision_radius){
-                break;
-            }
-            attempts += 1;
-            if attempts > 10{
-                panic!("Impossible to place balls to box")
-            }
-        }
-        
-        let speed = rng.sample::<f32, StandardNormal>(StandardNormal) * temperature.sqrt();
-        let angle: f32 = rng.random();
-        let speed_x = speed * angle.cos();
-        let speed_y = speed * angle.sin();
-        Ball{coord: Vec2{x, y}, speed: Vec2{x: speed_x, y: speed_y}, inside_maxwell: false}
-    }
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,353 @@
+use rand::Rng;
+
+use super::*;
+use crate::balance;
+use crate::scenario::Scenario;
+
+/// Velocities of all directions and of speeds up to 3, none along the partition
+fn velocities(n: usize, seed: u64) -> Vec<Vector>{
+    let mut noise = Noise::new(seed);
+    (0..n).map(|_| Vector::from_polar(3.0*noise.random::<Real>(), 2.0*PI*noise.random::<Real>()))
+        .filter(|v| v.x != 0.0)
+        .collect()
+}
+
+#[test]
+fn tennis_octants(){
+    // from the left, grazing balls pass swapped and steep ones are reflected
+    assert_eq!(tennis(Vector::new(1.0, 2.0)), Vector::new(2.0, 1.0));
+    assert_eq!(tennis(Vector::new(1.0, -2.0)), Vector::new(2.0, -1.0));
+    assert_eq!(tennis(Vector::new(2.0, 1.0)), Vector::new(-2.0, 1.0));
+    assert_eq!(tennis(Vector::new(2.0, -1.0)), Vector::new(-2.0, -1.0));
+    // from the right, steep balls pass swapped and grazing ones are reflected
+    assert_eq!(tennis(Vector::new(-2.0, 1.0)), Vector::new(-1.0, 2.0));
+    assert_eq!(tennis(Vector::new(-2.0, -1.0)), Vector::new(-1.0, -2.0));
+    assert_eq!(tennis(Vector::new(-1.0, 2.0)), Vector::new(1.0, 2.0));
+    assert_eq!(tennis(Vector::new(-1.0, -2.0)), Vector::new(1.0, -2.0));
+    // the boundaries are reflected, head-on balls from the right too
+    assert_eq!(tennis(Vector::new(1.0, 1.0)), Vector::new(-1.0, 1.0));
+    assert_eq!(tennis(Vector::new(-1.0, 1.0)), Vector::new(1.0, 1.0));
+    assert_eq!(tennis(Vector::new(1.0, 0.0)), Vector::new(-1.0, 0.0));
+    assert_eq!(tennis(Vector::new(-1.0, 0.0)), Vector::new(1.0, 0.0));
+}
+
+#[test]
+fn tennis_keeps_speed(){
+    for v in velocities(10_000, 1){
+        assert_eq!(tennis(v).length_sq(), v.length_sq(), "{v:?}");
+    }
+}
+
+#[test]
+fn tennis_is_a_bijection(){
+    for v in velocities(10_000, 2){
+        let out = tennis(v);
+        assert_ne!(out.x, 0.0, "{v:?} leaves along the partition");
+        // reversing the outgoing ball leads back, so no two velocities go to the same one
+        assert_eq!(tennis(-out), -v, "{v:?}");
+        // and every velocity comes out of some incoming one
+        let preimage = -tennis(-v);
+        assert_eq!(tennis(preimage), v, "{v:?}");
+    }
+}
+
+#[test]
+fn tennis_does_not_keep_phase_space_volume(){
+    let phase = balance::phase_space(MaxwellType::Tennis, 1.0, 1.0, 5_000, 3).unwrap();
+    // up to balls rounded over the boundaries between the octants
+    assert!(phase.irreversible <= 1e-3, "{phase:?}");
+    // every passing ball has its phase space stretched or squeezed, only the reflected half keeps it
+    assert!((0.4..0.6).contains(&phase.compressing), "{phase:?}");
+
+    // so of the equilibrium flux more passes from the right, steep, than from the left, grazing
+    let mut noise = Noise::new(4);
+    let (mut right, mut left) = (0, 0);
+    for _ in 0..100_000{
+        let across = (-2.0*(1.0 - noise.random::<Real>()).ln()).sqrt();
+        let along = noise.sample::<Real, StandardNormal>(StandardNormal);
+        let v = if noise.random::<bool>(){Vector::new(across, along)}else{Vector::new(-across, along)};
+        match (v.x > 0.0, tennis(v).x > 0.0){
+            (true, true) => right += 1,
+            (false, false) => left += 1,
+            _ => {}
+        }
+    }
+    // 1 - 1/√2 of the flux from the left passes, 1/√2 of the one from the right
+    assert!((left as f64/right as f64 - 2.414).abs() < 0.1, "{left} passed left, {right} right");
+}
+
+fn total(simulation: &Simulation) -> Exchange{
+    simulation.balls.iter().fold(Exchange::default(), |sum, b| sum + Exchange::of(simulation.mass(b), b.speed))
+}
+
+fn close(a: f64, b: f64, scale: f64) -> bool{
+    (a - b).abs() <= 1e-4*scale.max(1.0)
+}
+
+/// A box with the given demon and no balls, for placing them by hand
+fn empty_box(filter_type: MaxwellType) -> Simulation{
+    let mut simulation = Simulation::new();
+    simulation.random_initiation(&Parameters{filter_type, species: vec![Species{count: 0, ..Species::default()}], ..Parameters::default()}).unwrap();
+    simulation
+}
+
+#[test]
+fn collision_is_elastic(){
+    let mut simulation = empty_box(MaxwellType::Empty);
+    simulation.species = vec![Species{mass: 1.0, ..Species::default()}, Species{mass: 3.0, ..Species::default()}];
+    simulation.collision_radius = 0.05;
+    simulation.balls = vec![
+        Ball{coord: Vector::new(0.2, 0.5), speed: Vector::new(1.0, 0.3), inside_maxwell: false, species: 0},
+        Ball{coord: Vector::new(0.29, 0.52), speed: Vector::new(-0.5, 0.0), inside_maxwell: false, species: 1}
+    ];
+    let before = total(&simulation);
+    simulation.ball_collider(0.01);
+    let after = total(&simulation);
+    assert_ne!(simulation.balls[0].speed, Vector::new(1.0, 0.3), "the balls didn't collide");
+    assert!(close(before.energy, after.energy, before.energy), "{before:?} → {after:?}");
+    assert!(close(before.momentum[0], after.momentum[0], 1.0) && close(before.momentum[1], after.momentum[1], 1.0), "{before:?} → {after:?}");
+}
+
+#[test]
+fn empty_demon_is_a_hole(){
+    for v in velocities(1_000, 5){
+        let impact = Impact{position: 0.5, from_left: v.x > 0.0};
+        assert_eq!(transfer(MaxwellType::Empty, impact, v, 1.0, &mut Noise::default()), v);
+    }
+    // a ball aimed at the aperture flies through it untouched
+    let mut simulation = empty_box(MaxwellType::Empty);
+    simulation.collision_radius = 0.01;
+    let speed = Vector::new(1.0, 0.1);
+    simulation.balls.push(Ball{coord: Vector::new(0.3, 0.45), speed, inside_maxwell: false, species: 0});
+    for _ in 0..30{
+        simulation.step(0.01);
+    }
+    assert!(simulation.balls[0].coord.x > 0.55, "{:?}", simulation.balls[0]);
+    assert_eq!(simulation.balls[0].speed, speed);
+    assert_eq!((simulation.crossed_right, simulation.crossed_left), (1, 0));
+}
+
+#[test]
+fn empty_demon_splits_evenly(){
+    let mut simulation = Simulation::new();
+    simulation.random_initiation(&Parameters{filter_type: MaxwellType::Empty, seed: 6, ..Parameters::default()}).unwrap();
+    let (mut sum, mut samples) = (0.0, 0);
+    for i in 0..6_000{
+        simulation.step(0.01);
+        if i >= 500 && i % 10 == 0{
+            let (left, right) = simulation.structure.count_balls(&simulation);
+            sum += left as f64/(left + right) as f64;
+            samples += 1;
+        }
+    }
+    let mean = sum/samples as f64;
+    assert!((mean - 0.5).abs() < 0.03, "left fraction {mean}");
+}
+
+#[test]
+fn dense_packings_are_placed_on_a_lattice(){
+    // about 60% of the box, more than random additions reach
+    let parameters = Parameters{radius: 0.025, species: vec![Species{count: 300, ..Species::default()}], ..Parameters::default()};
+    let mut simulation = Simulation::new();
+    simulation.random_initiation(&parameters).unwrap();
+    assert_eq!(simulation.balls.len(), 300);
+    assert_no_overlaps(&simulation);
+}
+
+#[test]
+fn impossible_packings_fail(){
+    let mut simulation = Simulation::new();
+    simulation.random_initiation(&Parameters::default()).unwrap();
+    let before: Vec<_> = simulation.balls.iter().map(|b| (b.coord, b.speed)).collect();
+    let parameters = Parameters{radius: 0.05, filter_type: MaxwellType::Wall, species: vec![Species{count: 500, ..Species::default()}], ..Parameters::default()};
+    let error = simulation.random_initiation(&parameters).unwrap_err();
+    assert!(error.contains("don't fit"), "{error}");
+    // the running simulation is kept as it was
+    assert_eq!(simulation.balls.iter().map(|b| (b.coord, b.speed)).collect::<Vec<_>>(), before);
+    assert_eq!(simulation.structure.maxwell.filter_type, Parameters::default().filter_type);
+}
+
+fn assert_no_overlaps(simulation: &Simulation){
+    let r = simulation.collision_radius;
+    for (i, ball) in simulation.balls.iter().enumerate(){
+        assert!(!simulation.structure.in_bounds(ball.coord, r), "{ball:?}");
+        for other in &simulation.balls[..i]{
+            assert!((ball.coord - other.coord).length() >= 2.0*r, "{ball:?} overlaps {other:?}");
+        }
+    }
+}
+
+#[test]
+fn launch_directions_are_isotropic(){
+    let mut rng = StdRng::seed_from_u64(3);
+    let speeds: Vec<Vector> = (0..20_000).map(|_| maxwellian(1.0, &mut rng)).collect();
+    let mean = |f: fn(&Vector) -> Real| to_f64(speeds.iter().map(f).sum::<Real>())/speeds.len() as f64;
+    // every quadrant, not just the first one, with kT/2 in each direction
+    assert!(mean(|v| v.x).abs() < 0.02 && mean(|v| v.y).abs() < 0.02);
+    assert!((mean(|v| v.x*v.x) - 0.5).abs() < 0.02 && (mean(|v| v.y*v.y) - 0.5).abs() < 0.02);
+}
+
+#[test]
+fn one_chamber_starts_on_one_side(){
+    let parameters = Parameters{initial: InitialCondition::OneChamber{left: true}, ..Parameters::default()};
+    let mut simulation = Simulation::new();
+    simulation.random_initiation(&parameters).unwrap();
+    assert_eq!(simulation.balls.len(), parameters.balls_n());
+    assert!(simulation.balls.iter().all(|b| b.coord.x < simulation.structure.width/2.0));
+    assert_no_overlaps(&simulation);
+}
+
+#[test]
+fn initial_files_are_parsed(){
+    let balls = InitialCondition::parse("# x, y, vx, vy, species\n0.1, 0.2, 1, -1\n\n0.5,0.5,0,0.5,1\n").unwrap();
+    assert_eq!(balls, vec![
+        (Vector::new(0.1, 0.2), Vector::new(1.0, -1.0), 0),
+        (Vector::new(0.5, 0.5), Vector::new(0.0, 0.5), 1)
+    ]);
+    assert!(InitialCondition::parse("0.1, 0.2, 1").unwrap_err().starts_with("line 1"));
+    assert!(InitialCondition::parse("0.1, 0.2, 1, nan").is_err());
+}
+
+#[test]
+fn point_balls_at_rest_still_fall(){
+    let field = Field{gravity: 1.0, ..Field::default()};
+    let parameters = Parameters{radius: 0.0, temperature: 0.0, interaction: Interaction::Wca{epsilon: 1.0}, field, ..Parameters::default()};
+    let mut simulation = Simulation::new();
+    simulation.random_initiation(&parameters).unwrap();
+    simulation.step(0.01);
+    assert!(simulation.balls.iter().all(|b| b.speed.y != 0.0), "{:?}", simulation.balls[0]);
+}
+
+#[test]
+fn szilard_engine_needs_a_single_ball(){
+    let parameters = Parameters{szilard: Some(SzilardParameters::default()), ..Parameters::default()};
+    let error = Simulation::new().random_initiation(&parameters).unwrap_err();
+    assert!(error.contains("single ball"), "{error}");
+    let single = Parameters{species: vec![Species{count: 1, ..Species::default()}], ..parameters};
+    Simulation::new().random_initiation(&single).unwrap();
+}
+
+#[test]
+fn controlled_informed_demon_writes_only_while_acting(){
+    let informed = MaxwellType::Informed{capacity: 8, erasure: 0.1, from_gas: false};
+    let written = |target| {
+        let control = Control::Feedback{target, below: informed, above: MaxwellType::Wall};
+        let parameters = Parameters{filter_type: MaxwellType::Diode, control: Some(control), ..Parameters::default()};
+        let mut simulation = Simulation::new();
+        simulation.random_initiation(&parameters).unwrap();
+        for _ in 0..200{
+            simulation.step(0.01);
+        }
+        simulation.tape.expect("The controller may select the informed demon").written
+    };
+    // the left chamber never holds less than none of the balls, so the wall acts all the time
+    assert_eq!(written(0.0), 0);
+    assert!(written(1.0) > 0);
+}
+
+#[test]
+fn demons_a_controller_selects_are_validated(){
+    let broken = MaxwellType::Informed{capacity: 0, erasure: 0.1, from_gas: false};
+    let control = Control::Feedback{target: 0.5, below: MaxwellType::Diode, above: broken};
+    let mut scenario = Scenario::default();
+    scenario.parameters.control = Some(control);
+    assert!(scenario.validate().unwrap_err().contains("at least one bit"));
+}
+
+#[test]
+fn infinite_steps_and_windows_are_rejected(){
+    let error = Scenario::from_toml("dt = inf").unwrap_err();
+    assert!(error.contains("dt must be positive and finite"), "{error}");
+    let error = Scenario::from_toml("window = inf").unwrap_err();
+    assert!(error.contains("window must be positive and finite"), "{error}");
+    Scenario::default().validate().unwrap();
+}
+
+#[test]
+fn clamped_forces_derive_from_the_energy(){
+    let sigma = 0.02;
+    for interaction in [Interaction::LennardJones{epsilon: 1.0, cutoff: 2.5}, Interaction::Wca{epsilon: 1.0},
+            Interaction::SoftSphere{epsilon: 1.0, exponent: 12.0, cutoff: 2.0}]{
+        // across the closest distance the forces are evaluated at
+        for r in [0.3, 0.6, 0.69, 0.71, 0.9].map(|x| x*sigma){
+            let dr = 1e-3*sigma;
+            let slope = (interaction.pair(r - dr, sigma).0 - interaction.pair(r + dr, sigma).0)/(2.0*dr);
+            let force = interaction.pair(r, sigma).1*r;
+            assert!(close(to_f64(slope), to_f64(force), to_f64(force.abs())*100.0), "{interaction:?} at {r}: {slope} vs {force}");
+        }
+    }
+}
+
+/// Demons which never change the speed of a ball
+const KEEPING_SPEED: [MaxwellType; 7] = [
+    MaxwellType::Empty,
+    MaxwellType::Wall,
+    MaxwellType::Diode,
+    MaxwellType::Tennis,
+    MaxwellType::PhaseConserving{c: 0.3},
+    MaxwellType::Mirror{bottom: -0.3, top: 0.3},
+    MaxwellType::Graded{bottom: -0.5, top: 0.5}
+];
+
+proptest::proptest!{
+    #[test]
+    fn demons_keep_speed(x in -3.0..3.0f64, y in -3.0..3.0f64, position in 0.0..1.0f64, demon in 0..KEEPING_SPEED.len()){
+        let v = Vector::new(x as Real, y as Real);
+        let impact = Impact{position: position as Real, from_left: v.x > 0.0};
+        let out = transfer(KEEPING_SPEED[demon], impact, v, 1.0, &mut Noise::default());
+        proptest::prop_assert!(close(to_f64(out.length()), to_f64(v.length()), to_f64(v.length())), "{v:?} → {out:?}");
+    }
+
+    #[test]
+    fn collisions_conserve_energy_and_momentum(seed in 0..u64::MAX, count in 2..80u16, radius in 0.005..0.04f32, heavy in 0.2..5.0f32){
+        let species = vec![Species{count: count/2, ..Species::default()}, Species{count: count - count/2, mass: heavy, ..Species::default()}];
+        let mut simulation = Simulation::new();
+        simulation.random_initiation(&Parameters{seed, radius, species, ..Parameters::default()}).unwrap();
+        let before = total(&simulation);
+        for _ in 0..20{
+            simulation.ball_collider(0.01);
+        }
+        let after = total(&simulation);
+        proptest::prop_assert!(close(before.energy, after.energy, before.energy), "{before:?} → {after:?}");
+        let scale = before.energy.sqrt();
+        proptest::prop_assert!(close(before.momentum[0], after.momentum[0], scale) && close(before.momentum[1], after.momentum[1], scale), "{before:?} → {after:?}");
+    }
+
+    #[test]
+    fn balls_stay_in_their_space(
+        seed in 0..u64::MAX,
+        count in 1..60u16,
+        radius in 0.002..0.03f32,
+        wall_width in 0.0..0.2f32,
+        filter_height in 0.0..1.0f32,
+        demon in 0..KEEPING_SPEED.len(),
+        gravity in proptest::prop_oneof![proptest::strategy::Just(0.0f32), 0.1..2.0f32],
+        dt in 0.001..0.03f32
+    ){
+        let parameters = Parameters{
+            seed,
+            radius,
+            filter_type: KEEPING_SPEED[demon],
+            geometry: Geometry{wall_width, filter_height},
+            species: vec![Species{count, ..Species::default()}],
+            field: Field{gravity, ..Field::default()},
+            ..Parameters::default()
+        };
+        let mut simulation = Simulation::new();
+        simulation.random_initiation(&parameters).unwrap();
+        assert_no_overlaps(&simulation);
+        let energy = total(&simulation).energy + simulation.potential_energy;
+        for _ in 0..300{
+            simulation.step(dt);
+            for ball in &simulation.balls{
+                // outside the box and inside the partition, except for the aperture
+                proptest::prop_assert!(!simulation.structure.in_bounds(ball.coord, simulation.collision_radius), "{ball:?} at t = {}", simulation.time);
+            }
+        }
+        // none of these demons does any work, so without a field the energy stays
+        if gravity == 0.0{
+            let after = total(&simulation).energy;
+            proptest::prop_assert!(close(energy, after, energy), "{energy} → {after}");
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::vector::{Real, Vector, to_f64};
+
+/// Random positions tried for every ball before falling back to a lattice
+const ATTEMPTS: usize = 1000;
+/// Cells of the grid looking up neighbours, at most that many along a side
+const MAX_CELLS: Real = 512.0;
+
+/// Balls placed so far, binned into square cells at least a diameter wide
+struct Grid{
+    cell: Real,
+    columns: usize,
+    rows: usize,
+    cells: Vec<Vec<Vector>>
+}
+
+impl Grid{
+    fn new(width: Real, height: Real, radius: Real) -> Self{
+        let cell = (2.0*radius).max(width.max(height)/MAX_CELLS);
+        let columns = (width/cell).ceil() as usize + 1;
+        let rows = (height/cell).ceil() as usize + 1;
+        Self{cell, columns, rows, cells: vec![vec![]; columns*rows]}
+    }
+
+    fn index(&self, coord: Vector) -> (usize, usize){
+        (((coord.x/self.cell).max(0.0) as usize).min(self.columns - 1), ((coord.y/self.cell).max(0.0) as usize).min(self.rows - 1))
+    }
+
+    /// Whether a ball at `coord` would overlap one placed before
+    fn overlaps(&self, coord: Vector, radius: Real) -> bool{
+        let (column, row) = self.index(coord);
+        (row.saturating_sub(1)..(row + 2).min(self.rows)).any(|r|
+            (column.saturating_sub(1)..(column + 2).min(self.columns)).any(|c|
+                self.cells[r*self.columns + c].iter().any(|&other| (other - coord).length() < 2.0*radius)))
+    }
+
+    fn insert(&mut self, coord: Vector){
+        let (column, row) = self.index(coord);
+        self.cells[row*self.columns + column].push(coord);
+    }
+}
+
+/// Places `count` balls of `radius` in a `width` by `height` box without overlaps, where `free` allows them.
+///
+/// The balls are added one by one at random places. A packing too dense for that is placed on a shuffled
+/// hexagonal lattice instead, which melts once the balls move. Fails if even the lattice can't hold them
+pub fn place<R: Rng>(count: usize, width: Real, height: Real, radius: Real, free: impl Fn(Vector) -> bool, rng: &mut R) -> Result<Vec<Vector>, String>{
+    let mut grid = Grid::new(width, height, radius);
+    let mut placed = Vec::with_capacity(count);
+    'balls: while placed.len() < count{
+        for _ in 0..ATTEMPTS{
+            let coord = Vector::new(rng.random::<Real>()*width, rng.random::<Real>()*height);
+            if free(coord) && !grid.overlaps(coord, radius){
+                grid.insert(coord);
+                placed.push(coord);
+                continue 'balls
+            }
+        }
+        return lattice(count, width, height, radius, free, rng)
+    }
+    Ok(placed)
+}
+
+fn lattice<R: Rng>(count: usize, width: Real, height: Real, radius: Real, free: impl Fn(Vector) -> bool, rng: &mut R) -> Result<Vec<Vector>, String>{
+    // a little wider than the diameter, so rounding doesn't make neighbours overlap
+    let spacing = (2.0*radius*(1.0 + 1e-3)).max(Real::EPSILON.sqrt());
+    let row_height = spacing*(3.0 as Real).sqrt()/2.0;
+    let mut sites = vec![];
+    let mut y = radius;
+    let mut row = 0;
+    while y <= height{
+        let mut x = radius + if row % 2 == 1{spacing/2.0}else{0.0};
+        while x <= width{
+            let site = Vector::new(x, y);
+            if free(site){
+                sites.push(site);
+            }
+            x += spacing;
+        }
+        y += row_height;
+        row += 1;
+    }
+    if sites.len() < count{
+        let area = count as f64*std::f64::consts::PI*to_f64(radius).powi(2)/to_f64(width*height);
+        return Err(format!("{count} balls of radius {radius} don't fit in the box, at most {} do (they would cover {:.0}% of it)", sites.len(), 100.0*area))
+    }
+    sites.shuffle(rng);
+    sites.truncate(count);
+    Ok(sites)
+}
+
+/// Places `count` balls of `radius` evenly on the widest square lattice that has room for them where `free` allows.
+/// Fails if even the densest lattice hasn't
+pub fn regular(count: usize, width: Real, height: Real, radius: Real, free: impl Fn(Vector) -> bool) -> Result<Vec<Vector>, String>{
+    if count == 0{
+        return Ok(vec![])
+    }
+    let densest = (2.0*radius*(1.0 + 1e-3)).max(Real::EPSILON.sqrt());
+    let mut spacing = (width*height/count as Real).sqrt().max(densest);
+    loop{
+        let sites: Vec<Vector> = (0..(height/spacing) as usize)
+            .flat_map(|row| (0..(width/spacing) as usize).map(move |column|
+                Vector::new((column as Real + 0.5)*spacing, (row as Real + 0.5)*spacing)))
+            .filter(|&site| free(site))
+            .collect();
+        if sites.len() >= count{
+            // spread over all the sites, not packed into the first rows
+            return Ok((0..count).map(|i| sites[i*sites.len()/count]).collect())
+        }
+        if spacing <= densest{
+            return Err(format!("{count} balls of radius {radius} don't fit on a square lattice in the box, at most {} do", sites.len()))
+        }
+        spacing = (spacing*0.99).max(densest);
+    }
+}
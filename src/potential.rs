@@ -0,0 +1,133 @@
+use crate::vector::{Real, Vector};
+
+/// How the balls interact with each other, `σ` being the ball diameter.
+///
+/// Soft potentials are shifted to zero at their cut-off, so the energy does not jump there,
+/// and are integrated with velocity Verlet. They need a time step well below `σ/v`.
+/// The partition does not screen them.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum Interaction{
+    /// Elastic collisions of hard disks
+    #[default]
+    HardDisks,
+    /// `4ε((σ/r)¹² - (σ/r)⁶)`, cut off at `cutoff·σ`
+    LennardJones{epsilon: f32, cutoff: f32},
+    /// Repulsive part of Lennard-Jones, cut off at its minimum `2^(1/6)·σ`
+    Wca{epsilon: f32},
+    /// `ε(σ/r)ⁿ`, cut off at `cutoff·σ`
+    SoftSphere{epsilon: f32, exponent: f32, cutoff: f32}
+}
+
+/// Closest distance the forces are evaluated at, in units of `σ`.
+/// Closer pairs, e.g. overlapping after a random placement, are pushed apart by the force at this distance,
+/// and their energy grows linearly with it, so the energy is still conserved
+const CLOSEST: Real = 0.7;
+
+impl Interaction{
+    pub fn is_soft(self) -> bool{
+        self != Interaction::HardDisks
+    }
+
+    /// Distance beyond which balls don't interact
+    pub fn cutoff(self, sigma: Real) -> Real{
+        match self{
+            Interaction::HardDisks => sigma,
+            Interaction::LennardJones{cutoff, ..} | Interaction::SoftSphere{cutoff, ..} => cutoff as Real*sigma,
+            Interaction::Wca{..} => Real::powf(2.0, 1.0/6.0)*sigma
+        }
+    }
+
+    /// Unshifted energy and force divided by the distance, at distance `r`
+    fn raw(self, r: Real, sigma: Real) -> (Real, Real){
+        match self{
+            Interaction::HardDisks => (0.0, 0.0),
+            Interaction::LennardJones{epsilon, ..} | Interaction::Wca{epsilon} => {
+                let epsilon = epsilon as Real;
+                let s6 = (sigma/r).powi(6);
+                (4.0*epsilon*(s6*s6 - s6), 24.0*epsilon*(2.0*s6*s6 - s6)/(r*r))
+            },
+            Interaction::SoftSphere{epsilon, exponent, ..} => {
+                let (epsilon, n) = (epsilon as Real, exponent as Real);
+                let sn = (sigma/r).powf(n);
+                (epsilon*sn, n*epsilon*sn/(r*r))
+            }
+        }
+    }
+
+    /// Energy of a pair at distance `r` and the force on the first ball divided by `r`,
+    /// to be multiplied by the vector from the second ball to the first
+    pub fn pair(self, r: Real, sigma: Real) -> (Real, Real){
+        let cutoff = self.cutoff(sigma);
+        if r >= cutoff || !self.is_soft(){
+            return (0.0, 0.0)
+        }
+        let closest = CLOSEST*sigma;
+        let (energy, force) = self.raw(r.max(closest), sigma);
+        let shift = self.raw(cutoff, sigma).0;
+        if r >= closest{
+            return (energy - shift, force)
+        }
+        // below the closest distance the force keeps its magnitude, and the energy the work it does
+        let magnitude = force*closest;
+        (energy + magnitude*(closest - r) - shift, magnitude/r.max(Real::EPSILON))
+    }
+}
+
+/// Pairs of balls closer than the cut-off plus a skin, rebuilt once some ball moved by half the skin
+#[derive(Debug, Clone, Default)]
+pub struct Neighbours{
+    pairs: Vec<(usize, usize)>,
+    /// Positions at the last rebuild
+    reference: Vec<Vector>,
+    reach: Real
+}
+
+impl Neighbours{
+    pub fn pairs(&self) -> &[(usize, usize)]{
+        &self.pairs
+    }
+
+    /// Rebuilds the list if it may be missing a pair closer than `cutoff`
+    pub fn update(&mut self, positions: &[Vector], cutoff: Real, size: Vector){
+        let skin = 0.3*cutoff;
+        let stale = self.reference.len() != positions.len()
+            || self.reach != cutoff + skin
+            || positions.iter().zip(&self.reference).any(|(p, r)| (*p - *r).length_sq() > skin*skin/4.0);
+        if stale{
+            self.rebuild(positions, cutoff + skin, size);
+        }
+    }
+
+    /// Sorts the balls into cells at least `reach` wide and pairs balls of adjacent cells
+    fn rebuild(&mut self, positions: &[Vector], reach: Real, size: Vector){
+        // wider cells than needed are still correct, the cap only keeps the grid small for tiny balls
+        let columns = ((size.x/reach).floor() as usize).clamp(1, 256);
+        let rows = ((size.y/reach).floor() as usize).clamp(1, 256);
+        let cell = |p: Vector| {
+            let column = ((p.x/size.x*columns as Real).max(0.0) as usize).min(columns - 1);
+            let row = ((p.y/size.y*rows as Real).max(0.0) as usize).min(rows - 1);
+            (column, row)
+        };
+        let mut cells = vec![vec![]; columns*rows];
+        for (i, &p) in positions.iter().enumerate(){
+            let (column, row) = cell(p);
+            cells[row*columns + column].push(i);
+        }
+
+        self.pairs.clear();
+        for (i, &p) in positions.iter().enumerate(){
+            let (column, row) = cell(p);
+            for r in row.saturating_sub(1)..=(row + 1).min(rows - 1){
+                for c in column.saturating_sub(1)..=(column + 1).min(columns - 1){
+                    for &j in &cells[r*columns + c]{
+                        if j < i && (p - positions[j]).length_sq() < reach*reach{
+                            self.pairs.push((i, j));
+                        }
+                    }
+                }
+            }
+        }
+        self.reference = positions.to_vec();
+        self.reach = reach;
+    }
+}
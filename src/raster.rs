@@ -0,0 +1,204 @@
+use std::io::Write;
+
+use egui::{Color32, Pos2, Rect, Vec2};
+
+use crate::scene::{Scene, Style};
+
+/// Size and pacing of rendered frames
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct RecordingOptions{
+    pub width: u32,
+    pub height: u32,
+    /// Playback frame rate of the animation
+    pub fps: u32,
+    /// Simulated time between two frames
+    pub time_per_frame: f64,
+    pub style: Style
+}
+
+impl Default for RecordingOptions{
+    fn default() -> Self{
+        Self{width: 512, height: 512, fps: 30, time_per_frame: 0.05, style: Style::default()}
+    }
+}
+
+/// RGBA image with 8 bits per channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image{
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>
+}
+
+impl Image{
+    /// A `width` by `height` image filled with `background`. Fails for an empty or an overly large size
+    pub fn new(width: u32, height: u32, background: Color32) -> Result<Self, String>{
+        if width == 0 || height == 0{
+            return Err(format!("image size must be positive, got {width}x{height}"))
+        }
+        // four bytes a pixel
+        let bytes = width.checked_mul(height).and_then(|n| n.checked_mul(4))
+            .ok_or(format!("image of {width}x{height} is too large"))?;
+        let pixels = background.to_array().repeat(bytes as usize/4);
+        Ok(Self{width, height, pixels})
+    }
+
+    /// Draws `scene` scaled to fit the image, keeping the aspect ratio
+    pub fn render(scene: &Scene, width: u32, height: u32) -> Result<Self, String>{
+        let mut image = Self::new(width, height, scene.background)?;
+        let scale = (width as f32/scene.size.x).min(height as f32/scene.size.y);
+        let offset = (Vec2::new(width as f32, height as f32) - scene.size*scale)/2.0;
+        let to_image = |p: Pos2| (p.to_vec2()*scale + offset).to_pos2();
+
+        for block in &scene.blocks{
+            let rect = Rect::from_two_pos(to_image(block.rect.min), to_image(block.rect.max));
+            image.fill_rect(rect, block.fill);
+            image.stroke_rect(rect, block.stroke);
+        }
+        for segment in &scene.segments{
+            image.line(to_image(segment.from), to_image(segment.to), (segment.width*scale).max(1.0), segment.colour);
+        }
+        for disk in &scene.disks{
+            image.disk(to_image(disk.center), disk.radius*scale, disk.fill, disk.stroke);
+        }
+        image.stroke_rect(Rect::from_min_size(to_image(Pos2::ZERO), scene.size*scale), scene.border);
+        Ok(image)
+    }
+
+    /// Mixes `colour` into a pixel, `coverage` being the covered part of it
+    fn blend(&mut self, x: i64, y: i64, colour: Color32, coverage: f32){
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 || coverage <= 0.0{
+            return
+        }
+        let alpha = coverage.min(1.0)*colour.a() as f32/255.0;
+        let i = 4*(y as usize*self.width as usize + x as usize);
+        for (channel, value) in self.pixels[i..i + 3].iter_mut().zip(colour.to_srgba_unmultiplied()){
+            *channel = (*channel as f32*(1.0 - alpha) + value as f32*alpha).round() as u8;
+        }
+    }
+
+    /// Fills `rect` given in pixels, antialiasing partially covered pixels
+    pub fn fill_rect(&mut self, rect: Rect, colour: Color32){
+        for y in rect.min.y.floor() as i64..rect.max.y.ceil() as i64{
+            let cover_y = (rect.max.y.min(y as f32 + 1.0) - rect.min.y.max(y as f32)).max(0.0);
+            for x in rect.min.x.floor() as i64..rect.max.x.ceil() as i64{
+                let cover_x = (rect.max.x.min(x as f32 + 1.0) - rect.min.x.max(x as f32)).max(0.0);
+                self.blend(x, y, colour, cover_x*cover_y);
+            }
+        }
+    }
+
+    /// One pixel wide outline centered on the border of `rect`
+    pub fn stroke_rect(&mut self, rect: Rect, colour: Color32){
+        let (min, max) = (rect.min, rect.max);
+        self.fill_rect(Rect::from_min_max(Pos2::new(min.x - 0.5, min.y - 0.5), Pos2::new(max.x + 0.5, min.y + 0.5)), colour);
+        self.fill_rect(Rect::from_min_max(Pos2::new(min.x - 0.5, max.y - 0.5), Pos2::new(max.x + 0.5, max.y + 0.5)), colour);
+        self.fill_rect(Rect::from_min_max(Pos2::new(min.x - 0.5, min.y + 0.5), Pos2::new(min.x + 0.5, max.y - 0.5)), colour);
+        self.fill_rect(Rect::from_min_max(Pos2::new(max.x - 0.5, min.y + 0.5), Pos2::new(max.x + 0.5, max.y - 0.5)), colour);
+    }
+
+    /// Filled circle with a one pixel wide outline
+    pub fn disk(&mut self, center: Pos2, radius: f32, fill: Color32, stroke: Color32){
+        let reach = radius + 1.0;
+        for y in (center.y - reach).floor() as i64..=(center.y + reach).ceil() as i64{
+            for x in (center.x - reach).floor() as i64..=(center.x + reach).ceil() as i64{
+                let distance = Pos2::new(x as f32 + 0.5, y as f32 + 0.5).distance(center);
+                self.blend(x, y, fill, radius - distance + 0.5);
+                self.blend(x, y, stroke, 1.0 - (distance - radius).abs());
+            }
+        }
+    }
+
+    /// Line of `width` pixels with round ends
+    pub fn line(&mut self, from: Pos2, to: Pos2, width: f32, colour: Color32){
+        let reach = width/2.0 + 1.0;
+        let (min, max) = (from.min(to), from.max(to));
+        let direction = to - from;
+        let length_sq = direction.length_sq().max(f32::EPSILON);
+        for y in (min.y - reach).floor() as i64..=(max.y + reach).ceil() as i64{
+            for x in (min.x - reach).floor() as i64..=(max.x + reach).ceil() as i64{
+                let p = Pos2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let along = ((p - from).dot(direction)/length_sq).clamp(0.0, 1.0);
+                let distance = p.distance(from + direction*along);
+                self.blend(x, y, colour, width/2.0 - distance + 0.5);
+            }
+        }
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>, String>{
+        let mut out = vec![];
+        let mut encoder = png::Encoder::new(&mut out, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+        writer.write_image_data(&self.pixels).map_err(|e| e.to_string())?;
+        writer.finish().map_err(|e| e.to_string())?;
+        Ok(out)
+    }
+}
+
+/// Animated PNG encoded frame by frame, so only the frame being written is kept in memory
+pub struct ApngEncoder<W: Write>{
+    writer: png::Writer<W>,
+    width: u32,
+    height: u32
+}
+
+impl<W: Write> ApngEncoder<W>{
+    /// Starts a looping animation of `frames` frames of `width` by `height`, played at `fps`
+    pub fn new(out: W, width: u32, height: u32, frames: u32, fps: u32) -> Result<Self, String>{
+        let mut encoder = png::Encoder::new(out, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.validate_sequence(true);
+        encoder.set_animated(frames, 0)
+            .and_then(|_| encoder.set_frame_delay(1, fps.clamp(1, u16::MAX as u32) as u16))
+            .map_err(|e| e.to_string())?;
+        let writer = encoder.write_header().map_err(|e| e.to_string())?;
+        Ok(Self{writer, width, height})
+    }
+
+    /// Appends the next frame, which must be of the size the animation was started with
+    pub fn write_frame(&mut self, frame: &Image) -> Result<(), String>{
+        if (frame.width, frame.height) != (self.width, self.height){
+            return Err(format!("frame of {}x{} in an animation of {}x{}", frame.width, frame.height, self.width, self.height))
+        }
+        self.writer.write_image_data(&frame.pixels).map_err(|e| e.to_string())
+    }
+
+    /// Ends the animation. Fails if fewer frames were written than it was started with
+    pub fn finish(self) -> Result<(), String>{
+        self.writer.finish().map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn empty_and_huge_images_are_refused(){
+        assert!(Image::new(0, 10, Color32::BLACK).is_err());
+        assert!(Image::new(70_000, 70_000, Color32::BLACK).is_err());
+        let image = Image::new(3, 2, Color32::WHITE).unwrap();
+        assert_eq!(image.pixels.len(), 3*2*4);
+        assert!(image.to_png().unwrap().starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn animation_takes_exactly_its_frames(){
+        let frame = Image::new(4, 4, Color32::WHITE).unwrap();
+        let mut out = vec![];
+        let mut apng = ApngEncoder::new(&mut out, 4, 4, 2, 30).unwrap();
+        apng.write_frame(&frame).unwrap();
+        assert!(apng.write_frame(&Image::new(2, 4, Color32::WHITE).unwrap()).is_err());
+        apng.write_frame(&frame).unwrap();
+        apng.finish().unwrap();
+        assert!(out.starts_with(b"\x89PNG"));
+
+        let mut apng = ApngEncoder::new(vec![], 4, 4, 2, 30).unwrap();
+        apng.write_frame(&frame).unwrap();
+        assert!(apng.finish().is_err());
+    }
+}
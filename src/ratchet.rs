@@ -0,0 +1,267 @@
+use std::f64::consts::PI;
+
+use crate::scene::{Segment, Style};
+use crate::vector::{Real, Vector, from_f64, to_f64};
+
+/// Sizes and stiffnesses of the ratchet, see [`Ratchet`]
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct RatchetParameters{
+    /// Number of paddle vanes in the left chamber
+    pub vanes: u8,
+    pub vane_length: f32,
+    /// Moment of inertia of the paddle and the wheel together
+    pub inertia: f32,
+    pub teeth: u8,
+    /// Angle the pawl is lifted by on top of a tooth
+    pub tooth_height: f32,
+    /// Part of a tooth taken by its steep face
+    pub steep: f32,
+    pub pawl_length: f32,
+    pub pawl_inertia: f32,
+    /// Stiffness of the spring pressing the pawl onto the wheel
+    pub spring: f32,
+    /// Torque against the forward rotation, the work done on it is taken out of the box
+    pub load: f32
+}
+
+impl Default for RatchetParameters{
+    fn default() -> Self{
+        Self{
+            vanes: 4,
+            vane_length: 0.12,
+            inertia: 0.005,
+            teeth: 8,
+            tooth_height: 0.4,
+            steep: 0.15,
+            pawl_length: 0.12,
+            pawl_inertia: 0.002,
+            spring: 10.0,
+            load: 0.0
+        }
+    }
+}
+
+/// Radius of the wheel, the balls bounce off it as off a smooth disk
+const WHEEL: Real = 0.06;
+/// Stiffness of the contact between the pawl and the teeth
+const CONTACT: f64 = 2000.0;
+/// Part of the fastest oscillation period of the axle or the pawl a substep may take
+const SUBSTEP_PHASE: f64 = 0.02;
+const MAX_SUBSTEPS: f64 = 10000.0;
+
+/// Feynman–Smoluchowski ratchet: a paddle in the left chamber on one axle with a toothed wheel in the right one.
+///
+/// A pawl pressed onto the wheel by a spring lets it turn forward, lifting the pawl up the gentle faces
+/// of the teeth, and resists the backward turn up the steep faces. The balls hit the vanes and the pawl.
+/// With both chambers at the same temperature the pawl jumps over the teeth as often as it stops them,
+/// so on average the axle doesn't turn and lifts no load.
+///
+/// The axle and the pawl are integrated in double precision whatever [`Real`] is:
+/// their substeps are so short that single precision would round most of each one away.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+pub struct Ratchet{
+    pub parameters: RatchetParameters,
+    /// Angle of the axle within the current turn, growing in the direction the pawl lets it turn
+    pub angle: f64,
+    /// Whole turns of the axle, kept apart so the angle doesn't lose precision
+    pub revolutions: i64,
+    pub angular_velocity: f64,
+    /// Angle the pawl is lifted by
+    pub pawl: f64,
+    pub pawl_velocity: f64,
+    /// Axle of the paddle
+    pub hub: Vector,
+    /// Axle of the wheel
+    pub wheel: Vector,
+    /// Work done against [`RatchetParameters::load`] since the initiation
+    pub work: f64
+}
+
+impl Ratchet{
+    /// A ratchet at rest with the paddle at `hub` and the wheel at `wheel`
+    pub fn new(parameters: RatchetParameters, hub: Vector, wheel: Vector) -> Self{
+        Self{parameters, angle: 0.0, revolutions: 0, angular_velocity: 0.0, pawl: 0.0, pawl_velocity: 0.0, hub, wheel, work: 0.0}
+    }
+
+    /// Height the teeth lift the pawl to at axle angle `angle`, and its derivative
+    fn profile(&self, angle: f64) -> (f64, f64){
+        let p = &self.parameters;
+        let teeth = p.teeth.max(1) as f64;
+        let (height, steep) = (p.tooth_height as f64, (p.steep as f64).clamp(0.01, 0.99));
+        let phase = (angle*teeth/(2.0*PI)).rem_euclid(1.0);
+        let rate = teeth/(2.0*PI);
+        // half cosines, so the slope and with it the torque never jumps, which velocity Verlet needs
+        if phase < 1.0 - steep{
+            let u = PI*phase/(1.0 - steep);
+            (height*(1.0 - u.cos())/2.0, height*u.sin()/2.0*PI*rate/(1.0 - steep))
+        }
+        else{
+            let u = PI*(phase - 1.0 + steep)/steep;
+            (height*(1.0 + u.cos())/2.0, -height*u.sin()/2.0*PI*rate/steep)
+        }
+    }
+
+    /// Energy of the pawl pressed into a tooth and the torques it puts on the axle and on the pawl
+    fn contact(&self) -> (f64, f64, f64){
+        let (height, slope) = self.profile(self.angle);
+        let overlap = height - self.pawl;
+        if overlap <= 0.0{
+            return (0.0, 0.0, 0.0)
+        }
+        (CONTACT*overlap*overlap/2.0, -CONTACT*overlap*slope, CONTACT*overlap)
+    }
+
+    fn torques(&self) -> (f64, f64){
+        let (_, axle, pawl) = self.contact();
+        (axle - self.parameters.load as f64, pawl - self.parameters.spring as f64*self.pawl)
+    }
+
+    /// Kinetic energy of the axle and the pawl, and the potential energy of the spring and the contact
+    pub fn energy(&self) -> f64{
+        let p = &self.parameters;
+        let kinetic = p.inertia as f64*self.angular_velocity*self.angular_velocity/2.0
+            + p.pawl_inertia as f64*self.pawl_velocity*self.pawl_velocity/2.0;
+        kinetic + p.spring as f64*self.pawl*self.pawl/2.0 + self.contact().0
+    }
+
+    /// Turns of the axle since the initiation
+    pub fn turns(&self) -> f64{
+        self.revolutions as f64 + self.angle/(2.0*PI)
+    }
+
+    /// Integrates the axle and the pawl for `t` with velocity Verlet. Returns the work done against the load
+    pub fn advance(&mut self, t: Real) -> f64{
+        let p = self.parameters;
+        let (inertia, pawl_inertia) = (p.inertia as f64, p.pawl_inertia as f64);
+        let teeth = p.teeth.max(1) as f64;
+        let slope = p.tooth_height as f64*teeth/4.0/(p.steep as f64).clamp(0.01, 0.99);
+        let fastest = (CONTACT*slope*slope/inertia).sqrt() + ((CONTACT + p.spring as f64)/pawl_inertia).sqrt();
+        let t = to_f64(t);
+        let substeps = (t*fastest/SUBSTEP_PHASE).ceil().clamp(1.0, MAX_SUBSTEPS);
+        let h = t/substeps;
+        let start = self.work;
+        let (mut axle, mut pawl) = self.torques();
+        for _ in 0..substeps as u32{
+            self.angular_velocity += axle*h/2.0/inertia;
+            self.pawl_velocity += pawl*h/2.0/pawl_inertia;
+            self.angle += self.angular_velocity*h;
+            if !(0.0..2.0*PI).contains(&self.angle){
+                let turns = (self.angle/(2.0*PI)).floor();
+                self.revolutions += turns as i64;
+                self.angle -= turns*2.0*PI;
+            }
+            self.pawl += self.pawl_velocity*h;
+            self.work += p.load as f64*self.angular_velocity*h;
+            (axle, pawl) = self.torques();
+            self.angular_velocity += axle*h/2.0/inertia;
+            self.pawl_velocity += pawl*h/2.0/pawl_inertia;
+        }
+        self.work - start
+    }
+
+    /// Pivot of the pawl, level with the top of the wheel
+    fn pivot(&self) -> Vector{
+        self.wheel + Vector::new(self.parameters.pawl_length as Real, -WHEEL)
+    }
+
+    fn pawl_direction(&self) -> Vector{
+        Vector::from_polar(1.0, from_f64(PI + self.pawl))
+    }
+
+    fn vane_directions(&self) -> impl Iterator<Item = Vector> + '_{
+        let vanes = self.parameters.vanes.max(1);
+        (0..vanes).map(move |i| Vector::from_polar(1.0, from_f64(self.angle + 2.0*PI*i as f64/vanes as f64)))
+    }
+
+    /// Bounces a ball of `mass` at `coord` off the vanes, the pawl and the wheel.
+    /// Returns the momentum given to the ball, taken from the axles
+    pub fn collide(&mut self, coord: Vector, speed: &mut Vector, mass: Real, radius: Real) -> Vector{
+        let p = self.parameters;
+        let mut impulse = Vector::ZERO;
+        if (coord - self.hub).length() < p.vane_length as Real + radius{
+            let directions: Vec<Vector> = self.vane_directions().collect();
+            for direction in directions{
+                impulse += bounce(self.hub, direction, p.vane_length as Real, p.inertia as Real, &mut self.angular_velocity, coord, speed, mass, radius);
+            }
+        }
+        let (pivot, direction) = (self.pivot(), self.pawl_direction());
+        impulse += bounce(pivot, direction, p.pawl_length as Real, p.pawl_inertia as Real, &mut self.pawl_velocity, coord, speed, mass, radius);
+
+        let delta = coord - self.wheel;
+        let distance = delta.length();
+        if distance < WHEEL + radius && distance > 0.0{
+            let normal = delta/distance;
+            let approach = speed.dot(normal);
+            if approach < 0.0{
+                *speed -= normal*(2.0*approach);
+                impulse -= normal*(2.0*approach*mass);
+            }
+        }
+        impulse
+    }
+
+    /// Vanes, wheel outline and pawl, for the [`crate::scene::Scene`]
+    pub fn segments(&self, style: Style) -> Vec<Segment>{
+        let p = &self.parameters;
+        let colour = style.gray(160);
+        let line = |from: Vector, to: Vector, width: f32| Segment{from: from.to_pos2(), to: to.to_pos2(), width, colour};
+        let mut segments: Vec<Segment> = self.vane_directions()
+            .map(|d| line(self.hub, self.hub + d*p.vane_length as Real, 0.006))
+            .collect();
+
+        // the tooth under the pawl, at the top of the wheel, is the one of the current angle
+        let points = 24*p.teeth.max(1) as usize;
+        let outline: Vec<Vector> = (0..=points).map(|i| {
+            let angle = 2.0*PI*i as f64/points as f64;
+            let lift = (p.pawl_length as f64*self.profile(self.angle - angle - PI/2.0).0.sin()).max(0.0);
+            self.wheel + Vector::from_polar(WHEEL + from_f64(lift), from_f64(angle))
+        }).collect();
+        segments.extend(outline.windows(2).map(|w| line(w[0], w[1], 0.003)));
+
+        let pivot = self.pivot();
+        segments.push(line(pivot, pivot + self.pawl_direction()*p.pawl_length as Real, 0.006));
+        segments
+    }
+}
+
+/// Bounces a ball off an arm of `length` turning around `pivot` with `angular_velocity`,
+/// conserving the energy and the angular momentum around the pivot. Returns the momentum given to the ball
+#[allow(clippy::too_many_arguments)]
+fn bounce(pivot: Vector, direction: Vector, length: Real, inertia: Real, angular_velocity: &mut f64,
+    coord: Vector, speed: &mut Vector, mass: Real, radius: Real) -> Vector{
+    let arm = direction*(coord - pivot).dot(direction).clamp(0.0, length);
+    let delta = coord - pivot - arm;
+    let distance = delta.length();
+    if distance >= radius || distance <= 0.0{
+        return Vector::ZERO
+    }
+    let normal = delta/distance;
+    let lever = arm.x*normal.y - arm.y*normal.x;
+    let surface = Vector::new(-arm.y, arm.x)*from_f64(*angular_velocity);
+    let approach = (*speed - surface).dot(normal);
+    if approach >= 0.0{
+        return Vector::ZERO
+    }
+    let j = -2.0*approach/(1.0/mass + lever*lever/inertia);
+    *speed += normal*(j/mass);
+    *angular_velocity -= to_f64(j*lever/inertia);
+    normal*j
+}
+
+#[cfg(test)]
+mod tests{
+    use crate::headless::Run;
+    use crate::observables::Observable;
+    use crate::scenario::Scenario;
+
+    #[test]
+    fn ratchet_does_not_turn_at_one_temperature(){
+        let scenario = Scenario::bundled("ratchet").unwrap();
+        let mut run = Run::from_scenario(&scenario).unwrap();
+        run.run_for(200.0);
+        let rotation = run.recorder.steady_state(Observable::Rotation).expect("the rotation settles");
+        assert!(rotation.mean.abs() <= rotation.half_width, "{} ± {}", rotation.mean, rotation.half_width);
+        assert_eq!(run.diagnostics.flagged_count(), 0);
+    }
+}
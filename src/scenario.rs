@@ -0,0 +1,224 @@
+use base64::Engine as _;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+
+use crate::field::ExternalPotential;
+use crate::observables::Observable;
+use crate::control::Control;
+use crate::physics::{InitialCondition, Kernel, MaxwellType, Parameters};
+use crate::potential::Interaction;
+
+/// Complete description of an experiment, as stored in TOML files
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Scenario{
+    pub name: String,
+    pub description: String,
+    #[serde(flatten)]
+    pub parameters: Parameters,
+    /// Time step of the simulation
+    pub dt: f32,
+    /// Simulated time of a headless run
+    pub duration: f64,
+    /// Simulated time every recorded sample is averaged over
+    pub window: f64,
+    /// Columns of the exported time series
+    pub observables: Vec<Observable>
+}
+
+impl Default for Scenario{
+    fn default() -> Self{
+        Self{
+            name: String::new(),
+            description: String::new(),
+            parameters: Parameters::default(),
+            dt: 0.01,
+            duration: 60.0,
+            window: 0.3,
+            observables: Observable::ALL.to_vec()
+        }
+    }
+}
+
+/// The presets, the first four also available with keys 1-4 in the app
+pub const BUNDLED: [(&str, &str); 18] = [
+    ("tennis", include_str!("../scenarios/tennis.toml")),
+    ("phase_conserving", include_str!("../scenarios/phase_conserving.toml")),
+    ("diode", include_str!("../scenarios/diode.toml")),
+    ("empty", include_str!("../scenarios/empty.toml")),
+    ("lennard_jones", include_str!("../scenarios/lennard_jones.toml")),
+    ("barometric", include_str!("../scenarios/barometric.toml")),
+    ("diode_against_field", include_str!("../scenarios/diode_against_field.toml")),
+    ("ratchet", include_str!("../scenarios/ratchet.toml")),
+    ("ratchet_engine", include_str!("../scenarios/ratchet_engine.toml")),
+    ("szilard", include_str!("../scenarios/szilard.toml")),
+    ("informed", include_str!("../scenarios/informed.toml")),
+    ("stochastic", include_str!("../scenarios/stochastic.toml")),
+    ("graded", include_str!("../scenarios/graded.toml")),
+    ("mirror", include_str!("../scenarios/mirror.toml")),
+    ("feedback", include_str!("../scenarios/feedback.toml")),
+    ("temperature", include_str!("../scenarios/temperature.toml")),
+    ("free_expansion", include_str!("../scenarios/free_expansion.toml")),
+    ("two_temperatures", include_str!("../scenarios/two_temperatures.toml")),
+];
+
+impl Scenario{
+    pub fn from_toml(text: &str) -> Result<Self, String>{
+        let scenario: Self = toml::from_str(text).map_err(|e| e.to_string())?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    /// One of the [`BUNDLED`] scenarios, by its name
+    pub fn bundled(name: &str) -> Option<Self>{
+        BUNDLED.iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, text)| Self::from_toml(text).expect("Bundled scenarios are valid"))
+    }
+
+    /// Reads a scenario file, together with the file of its initial balls if it has one
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: &str) -> Result<Self, String>{
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        let mut scenario = Self::from_toml(&text).map_err(|e| format!("{path}: {e}"))?;
+        scenario.parameters.initial = scenario.parameters.initial.load()?;
+        Ok(scenario)
+    }
+
+    pub fn to_toml(&self) -> String{
+        toml::to_string(self).expect("Scenarios are always serializable")
+    }
+
+    /// Compact form for the fragment of a link, without the leading `#`
+    pub fn to_fragment(&self) -> String{
+        let json = serde_json::to_string(self).expect("Scenarios are always serializable");
+        format!("scenario={}", URL_SAFE_NO_PAD.encode(json))
+    }
+
+    /// Reads what [`Scenario::to_fragment`] wrote, `None` if there is no scenario in the fragment
+    pub fn from_fragment(fragment: &str) -> Option<Result<Self, String>>{
+        let encoded = fragment.trim_start_matches('#').strip_prefix("scenario=")?;
+        Some((|| {
+            let json = URL_SAFE_NO_PAD.decode(encoded).map_err(|e| format!("Broken link: {e}"))?;
+            let scenario: Self = serde_json::from_slice(&json).map_err(|e| format!("Broken link: {e}"))?;
+            scenario.validate()?;
+            Ok(scenario)
+        })())
+    }
+
+    /// Rejects values the simulation can't run with
+    pub fn validate(&self) -> Result<(), String>{
+        let p = &self.parameters;
+        if !self.dt.is_finite() || self.dt <= 0.0{
+            return Err(format!("dt must be positive and finite, got {}", self.dt))
+        }
+        if !self.duration.is_finite() || self.duration <= 0.0{
+            return Err(format!("duration must be positive and finite, got {}", self.duration))
+        }
+        if !self.window.is_finite() || self.window <= 0.0{
+            return Err(format!("window must be positive and finite, got {}", self.window))
+        }
+        if p.temperature.is_nan() || p.temperature < 0.0{
+            return Err(format!("temperature can't be negative, got {}", p.temperature))
+        }
+        if p.radius.is_nan() || p.radius < 0.0{
+            return Err(format!("radius can't be negative, got {}", p.radius))
+        }
+        if !(0.0..=1.0).contains(&p.geometry.filter_height){
+            return Err(format!("filter_height must be between 0 and 1, got {}", p.geometry.filter_height))
+        }
+        if !(0.0..1.0).contains(&p.geometry.wall_width){
+            return Err(format!("wall_width must be between 0 and 1, got {}", p.geometry.wall_width))
+        }
+        if let Some(s) = p.species.iter().find(|s| s.mass.is_nan() || s.mass <= 0.0){
+            return Err(format!("mass of species \"{}\" must be positive, got {}", s.name, s.mass))
+        }
+        match &p.initial{
+            InitialCondition::TwoTemperatures{left, right} if [left, right].iter().any(|t| t.is_nan() || **t < 0.0) =>
+                return Err(format!("chamber temperatures can't be negative, got {left} and {right}")),
+            InitialCondition::File{path} if path.is_empty() => return Err("the file of the initial balls needs a path".into()),
+            _ => {}
+        }
+        match p.control{
+            Some(Control::Schedule{open, closed}) if !(open >= 0.0 && closed >= 0.0 && open + closed > 0.0) =>
+                return Err(format!("the door must be open or closed for some time, got {open} and {closed}")),
+            Some(Control::Feedback{target, ..}) if !(0.0..=1.0).contains(&target) =>
+                return Err(format!("feedback target must be between 0 and 1, got {target}")),
+            _ => {}
+        }
+        for demon in p.demons(){
+            validate_demon(demon)?;
+        }
+        match p.interaction{
+            Interaction::HardDisks => {},
+            _ if p.radius.is_nan() || p.radius <= 0.0 => return Err("soft interactions need a positive radius".into()),
+            Interaction::LennardJones{epsilon, ..} | Interaction::Wca{epsilon} | Interaction::SoftSphere{epsilon, ..}
+                if epsilon.is_nan() || epsilon < 0.0 => return Err(format!("epsilon can't be negative, got {epsilon}")),
+            Interaction::LennardJones{cutoff, ..} | Interaction::SoftSphere{cutoff, ..} if cutoff.is_nan() || cutoff <= 0.0 =>
+                return Err(format!("cutoff must be positive, got {cutoff}")),
+            Interaction::SoftSphere{exponent, ..} if exponent.is_nan() || exponent <= 0.0 =>
+                return Err(format!("exponent must be positive, got {exponent}")),
+            _ => {}
+        }
+        if let ExternalPotential::Periodic{wavelength, ..} = p.field.potential && (wavelength.is_nan() || wavelength <= 0.0){
+            return Err(format!("wavelength must be positive, got {wavelength}"))
+        }
+        if p.field.gravity.is_nan() || p.field.electric.iter().any(|e| e.is_nan()){
+            return Err("field can't be NaN".into())
+        }
+        if let Some(t) = [p.thermal_walls.left, p.thermal_walls.right].into_iter().flatten().find(|t| t.is_nan() || *t < 0.0){
+            return Err(format!("wall temperature can't be negative, got {t}"))
+        }
+        if let Some(s) = &p.szilard{
+            // given balls are counted when the simulation starts
+            if p.balls_n() != 1 && !matches!(p.initial, InitialCondition::Given{..}){
+                return Err(format!("the Szilard engine runs a single ball, got {}", p.balls_n()))
+            }
+            if s.piston_speed.is_nan() || s.piston_speed <= 0.0 || s.idle.is_nan() || s.idle < 0.0{
+                return Err("the piston speed must be positive and the idle time can't be negative".into())
+            }
+        }
+        if let Some(r) = &p.ratchet{
+            let sizes = [r.vane_length, r.inertia, r.pawl_length, r.pawl_inertia, r.tooth_height];
+            if sizes.iter().any(|x| x.is_nan() || *x <= 0.0){
+                return Err("ratchet lengths, inertias and tooth height must be positive".into())
+            }
+            if r.vanes == 0 || r.teeth == 0{
+                return Err("the ratchet needs at least a vane and a tooth".into())
+            }
+            if r.steep.is_nan() || r.steep <= 0.0 || r.steep >= 1.0{
+                return Err(format!("steep must be between 0 and 1, got {}", r.steep))
+            }
+            if r.spring.is_nan() || r.spring < 0.0 || !r.load.is_finite(){
+                return Err("ratchet spring can't be negative and the load must be finite".into())
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks the parameters of a single demon
+fn validate_demon(demon: MaxwellType) -> Result<(), String>{
+    if let MaxwellType::Temperature{fast, slow, ..} = demon && [fast, slow].iter().any(|e| e.is_nan() || *e < 0.0){
+        return Err(format!("temperature demon thresholds can't be negative, got {fast} and {slow}"))
+    }
+    match demon{
+        MaxwellType::Stochastic(Kernel::Transmission{right, left}) if !(0.0..=1.0).contains(&right) || !(0.0..=1.0).contains(&left) =>
+            return Err(format!("transmission probabilities must be between 0 and 1, got {right} and {left}")),
+        MaxwellType::Stochastic(Kernel::Thermalizing{temperature}) if temperature.is_nan() || temperature < 0.0 =>
+            return Err(format!("kernel temperature can't be negative, got {temperature}")),
+        MaxwellType::Graded{bottom, top} if !bottom.is_finite() || !top.is_finite() =>
+            return Err(format!("graded demon constants must be finite, got {bottom} and {top}")),
+        MaxwellType::Mirror{bottom, top} if [bottom, top].iter().any(|a| a.is_nan() || a.abs() >= std::f32::consts::FRAC_PI_2) =>
+            return Err(format!("mirror tilts must be within a right angle, got {bottom} and {top}")),
+        _ => {}
+    }
+    if let MaxwellType::Informed{capacity, erasure, ..} = demon{
+        if capacity == 0{
+            return Err("the demon tape needs at least one bit".into())
+        }
+        if erasure.is_nan() || erasure < 0.0{
+            return Err(format!("erasure cost can't be negative, got {erasure}"))
+        }
+    }
+    Ok(())
+}
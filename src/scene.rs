@@ -0,0 +1,161 @@
+use std::collections::VecDeque;
+
+use eframe::emath::RectTransform;
+use egui::{Color32, Painter, Pos2, Rect, Stroke, StrokeKind, Vec2};
+
+/// How the balls are coloured
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum Colouring{
+    /// Grey balls
+    #[default]
+    Plain,
+    /// From blue for slow balls to red for fast ones, relative to the mean square speed
+    Speed,
+    /// One colour for the left chamber, another one for the right
+    Chamber,
+    /// A colour per [`crate::physics::Species`]
+    Species
+}
+
+/// Colours used to draw a [`Scene`]
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct Style{
+    pub dark_mode: bool,
+    pub colouring: Colouring
+}
+
+impl Default for Style{
+    fn default() -> Self{
+        Self{dark_mode: true, colouring: Colouring::Plain}
+    }
+}
+
+impl Style{
+    /// Grey of the given lightness in the dark theme, inverted for the light one
+    pub fn gray(&self, c: u8) -> Color32{
+        Color32::from_gray(if self.dark_mode{c}else{c.overflowing_neg().0})
+    }
+
+    /// `speed_ratio` is the ball speed divided by the root mean square speed
+    pub fn ball_fill(&self, speed_ratio: f32, left: bool, species: usize) -> Color32{
+        match self.colouring{
+            Colouring::Plain => self.gray(128),
+            Colouring::Speed => {
+                let slow = Color32::from_rgb(60, 110, 230);
+                let fast = Color32::from_rgb(230, 70, 50);
+                slow.lerp_to_gamma(fast, (speed_ratio/2.0).clamp(0.0, 1.0))
+            },
+            Colouring::Chamber => if left{
+                Color32::from_rgb(80, 150, 230)
+            }
+            else{
+                Color32::from_rgb(230, 150, 60)
+            },
+            Colouring::Species => {
+                const PALETTE: [Color32; 6] = [
+                    Color32::from_rgb(128, 128, 128),
+                    Color32::from_rgb(230, 150, 60),
+                    Color32::from_rgb(80, 150, 230),
+                    Color32::from_rgb(110, 190, 90),
+                    Color32::from_rgb(200, 90, 180),
+                    Color32::from_rgb(220, 200, 70)
+                ];
+                PALETTE[species % PALETTE.len()]
+            }
+        }
+    }
+}
+
+/// Axis-aligned rectangle of the box: the partition or the demon aperture
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Block{
+    pub rect: Rect,
+    pub fill: Color32,
+    pub stroke: Color32
+}
+
+/// Straight line of the given width, e.g. a paddle vane
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment{
+    pub from: Pos2,
+    pub to: Pos2,
+    pub width: f32,
+    pub colour: Color32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Disk{
+    pub center: Pos2,
+    pub radius: f32,
+    pub velocity: Vec2,
+    pub fill: Color32,
+    pub stroke: Color32
+}
+
+/// Everything drawn for a simulation state, in simulation coordinates.
+///
+/// The same scene is painted on the screen, rasterised and written as SVG.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scene{
+    /// Size of the box, the scene spans from the origin to it
+    pub size: Vec2,
+    pub background: Color32,
+    pub border: Color32,
+    /// Drawn in order, so the aperture goes over the partition
+    pub blocks: Vec<Block>,
+    /// Moving parts, e.g. of the ratchet, drawn over the blocks
+    pub segments: Vec<Segment>,
+    pub disks: Vec<Disk>
+}
+
+impl Scene{
+    pub fn paint(&self, painter: &Painter, transform: RectTransform){
+        let scale = transform.scale().x;
+        for block in &self.blocks{
+            painter.rect(Rect::from_two_pos(transform*block.rect.min, transform*block.rect.max), 1.0, block.fill, Stroke::new(1.0, block.stroke), StrokeKind::Middle);
+        }
+        for segment in &self.segments{
+            painter.line_segment([transform*segment.from, transform*segment.to], Stroke::new(scale*segment.width, segment.colour));
+        }
+        for disk in &self.disks{
+            painter.circle(transform*disk.center, scale*disk.radius, disk.fill, Stroke::new(1.0, disk.stroke));
+        }
+    }
+}
+
+/// Recent positions of every ball, to show where they came from
+#[derive(Debug, Clone, Default)]
+pub struct Trails{
+    /// Number of positions kept per ball
+    pub length: usize,
+    paths: Vec<VecDeque<Pos2>>
+}
+
+impl Trails{
+    pub fn new(length: usize) -> Self{
+        Self{length, paths: vec![]}
+    }
+
+    /// Appends the current ball positions, starting over if the balls changed
+    pub fn push(&mut self, scene: &Scene){
+        if self.paths.len() != scene.disks.len(){
+            self.paths = vec![VecDeque::with_capacity(self.length); scene.disks.len()];
+        }
+        for (path, disk) in self.paths.iter_mut().zip(&scene.disks){
+            path.push_back(disk.center);
+            while path.len() > self.length{
+                path.pop_front();
+            }
+        }
+    }
+
+    pub fn clear(&mut self){
+        self.paths.clear();
+    }
+
+    /// Positions of every ball, oldest first
+    pub fn paths(&self) -> &[VecDeque<Pos2>]{
+        &self.paths
+    }
+}
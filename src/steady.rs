@@ -0,0 +1,129 @@
+/// Where a series of samples settles.
+///
+/// The start of the steady state is found with the marginal standard error rule (MSER):
+/// the truncation minimising the standard error of the mean of what is left.
+/// The confidence interval comes from the means of consecutive batches of the remaining samples,
+/// as the samples themselves are correlated.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct SteadyState{
+    /// Time of the first sample of the steady state
+    pub relaxation_time: f64,
+    /// Mean over the steady state
+    pub mean: f64,
+    /// Half width of the 95% confidence interval of the mean
+    pub half_width: f64,
+    /// Number of samples in the steady state
+    pub samples: usize
+}
+
+/// Batches of the steady state used for the confidence interval
+const BATCHES: usize = 10;
+/// 97.5% quantile of Student's t distribution with `BATCHES - 1` degrees of freedom
+const T_QUANTILE: f64 = 2.262;
+
+impl SteadyState{
+    /// Fewest samples a series needs to be judged
+    pub const MIN_SAMPLES: usize = 4*BATCHES;
+
+    /// Detects the steady state of `values` sampled at `times`.
+    ///
+    /// `None` if there are too few samples, some are not finite,
+    /// or the series still drifts: the best truncation lies in its second half.
+    pub fn detect(times: &[f64], values: &[f64]) -> Option<Self>{
+        let n = values.len().min(times.len());
+        if n < Self::MIN_SAMPLES || values[..n].iter().any(|v| !v.is_finite()){
+            return None
+        }
+
+        // sums of the values and of their squares from every index to the end
+        let mut sum = vec![0.0; n + 1];
+        let mut squares = vec![0.0; n + 1];
+        for i in (0..n).rev(){
+            sum[i] = sum[i + 1] + values[i];
+            squares[i] = squares[i + 1] + values[i]*values[i];
+        }
+        let mser = |d: usize| {
+            let left = (n - d) as f64;
+            let variance = (squares[d] - sum[d]*sum[d]/left).max(0.0);
+            variance/(left*left)
+        };
+        // the rule is known to pick spurious truncations at the very end, so the end is not searched
+        let start = (0..n - Self::MIN_SAMPLES/2).min_by(|&a, &b| mser(a).total_cmp(&mser(b)))?;
+        if start > n/2{
+            return None
+        }
+
+        let steady = &values[start..n];
+        let mean = steady.iter().sum::<f64>()/steady.len() as f64;
+        let batch = steady.len()/BATCHES;
+        let batch_means: Vec<f64> = steady.chunks_exact(batch).take(BATCHES)
+            .map(|b| b.iter().sum::<f64>()/batch as f64)
+            .collect();
+        let batch_mean = batch_means.iter().sum::<f64>()/BATCHES as f64;
+        let variance = batch_means.iter().map(|m| (m - batch_mean).powi(2)).sum::<f64>()/(BATCHES - 1) as f64;
+        Some(Self{
+            relaxation_time: times[start],
+            mean,
+            half_width: T_QUANTILE*(variance/BATCHES as f64).sqrt(),
+            samples: steady.len()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use rand::Rng;
+    use rand_distr::StandardNormal;
+
+    use super::*;
+    use crate::noise::Noise;
+
+    /// `count` samples a tenth apart of `signal` plus gaussian noise of deviation `sigma`
+    fn series(count: usize, sigma: f64, seed: u64, signal: impl Fn(f64) -> f64) -> (Vec<f64>, Vec<f64>){
+        let mut noise = Noise::new(seed);
+        let times: Vec<f64> = (0..count).map(|i| 0.1*i as f64).collect();
+        let values = times.iter().map(|&t| signal(t) + sigma*noise.sample::<f64, _>(StandardNormal)).collect();
+        (times, values)
+    }
+
+    #[test]
+    fn exponential_relaxation_is_cut_off(){
+        // the transient falls below the noise at about t = 2 ln 30 ≈ 7
+        let (times, values) = series(1000, 0.1, 1, |t| 5.0 + 3.0*(-t/2.0).exp());
+        let steady = SteadyState::detect(&times, &values).unwrap();
+        assert!((3.0..15.0).contains(&steady.relaxation_time), "{steady:?}");
+        assert!((steady.mean - 5.0).abs() < steady.half_width, "{steady:?}");
+        assert!(steady.half_width < 0.05, "{steady:?}");
+        assert_eq!(steady.samples, values.len() - (steady.relaxation_time*10.0).round() as usize);
+    }
+
+    #[test]
+    fn confidence_interval_covers_the_mean(){
+        let runs = 200u64;
+        let covered = (0..runs).filter(|&seed| {
+            let (times, values) = series(400, 1.0, seed, |_| 2.0);
+            let steady = SteadyState::detect(&times, &values).unwrap();
+            assert!(steady.relaxation_time < 20.0, "{steady:?}");
+            (steady.mean - 2.0).abs() <= steady.half_width
+        }).count() as u64;
+        // 95% of them, give or take the sampling noise of 200 runs
+        assert!(covered >= runs*88/100, "{covered} of {runs}");
+    }
+
+    #[test]
+    fn drift_does_not_settle(){
+        for seed in 0..20{
+            let (times, values) = series(500, 0.1, seed, |t| 0.5*t);
+            assert_eq!(SteadyState::detect(&times, &values), None);
+        }
+    }
+
+    #[test]
+    fn short_or_broken_series_are_not_judged(){
+        let (times, values) = series(SteadyState::MIN_SAMPLES - 1, 0.1, 0, |_| 1.0);
+        assert_eq!(SteadyState::detect(&times, &values), None);
+        let (times, mut values) = series(100, 0.1, 0, |_| 1.0);
+        values[50] = f64::NAN;
+        assert_eq!(SteadyState::detect(&times, &values), None);
+    }
+}
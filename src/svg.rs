@@ -0,0 +1,88 @@
+use std::fmt::Write;
+
+use egui::{Color32, Pos2, Rect};
+
+use crate::scene::{Scene, Trails};
+
+/// What to draw besides the box and the balls
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct SvgOptions{
+    /// Size of the longest side of the picture
+    pub size: f32,
+    pub trails: bool,
+    pub velocity_arrows: bool,
+    /// Simulated time an arrow spans: a ball reaches the arrow tip after this time
+    pub arrow_time: f32
+}
+
+impl Default for SvgOptions{
+    fn default() -> Self{
+        Self{size: 512.0, trails: false, velocity_arrows: false, arrow_time: 0.05}
+    }
+}
+
+fn colour(c: Color32) -> String{
+    let [r, g, b, _] = c.to_srgba_unmultiplied();
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// Writes `scene` as an SVG document, with the same geometry as on the screen.
+/// `trails` are drawn only if enabled in `options`.
+pub fn to_svg(scene: &Scene, trails: Option<&Trails>, options: &SvgOptions) -> String{
+    let scale = options.size/scene.size.x.max(scene.size.y);
+    let (width, height) = (scene.size.x*scale, scene.size.y*scale);
+    let to_svg = |p: Pos2| (p.x*scale, p.y*scale);
+    let mut out = String::new();
+
+    let _ = writeln!(out, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#);
+    if options.velocity_arrows{
+        let _ = writeln!(out, r#"<defs><marker id="arrow" viewBox="0 0 10 10" refX="10" refY="5" markerWidth="5" markerHeight="5" orient="auto-start-reverse"><path d="M 0 0 L 10 5 L 0 10 z" fill="context-stroke"/></marker></defs>"#);
+    }
+    let _ = writeln!(out, r#"<rect width="{width}" height="{height}" fill="{}"/>"#, colour(scene.background));
+
+    for block in &scene.blocks{
+        let rect = Rect::from_two_pos(block.rect.min, block.rect.max);
+        let (x, y) = to_svg(rect.min);
+        let _ = writeln!(out, r#"<rect x="{x}" y="{y}" width="{}" height="{}" fill="{}" stroke="{}" stroke-width="1"/>"#,
+            rect.width()*scale, rect.height()*scale, colour(block.fill), colour(block.stroke));
+    }
+
+    for segment in &scene.segments{
+        let (x1, y1) = to_svg(segment.from);
+        let (x2, y2) = to_svg(segment.to);
+        let _ = writeln!(out, r#"<line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}" stroke="{}" stroke-width="{}" stroke-linecap="round"/>"#,
+            colour(segment.colour), segment.width*scale);
+    }
+
+    if options.trails && let Some(trails) = trails{
+        for (path, disk) in trails.paths().iter().zip(&scene.disks){
+            if path.len() < 2{
+                continue
+            }
+            let points = path.iter().map(|&p| {
+                let (x, y) = to_svg(p);
+                format!("{x},{y}")
+            }).collect::<Vec<_>>().join(" ");
+            let _ = writeln!(out, r#"<polyline points="{points}" fill="none" stroke="{}" stroke-opacity="0.5" stroke-width="1"/>"#, colour(disk.fill));
+        }
+    }
+
+    for disk in &scene.disks{
+        let (x, y) = to_svg(disk.center);
+        let _ = writeln!(out, r#"<circle cx="{x}" cy="{y}" r="{}" fill="{}" stroke="{}" stroke-width="1"/>"#,
+            disk.radius*scale, colour(disk.fill), colour(disk.stroke));
+    }
+
+    if options.velocity_arrows{
+        for disk in &scene.disks{
+            let (x1, y1) = to_svg(disk.center);
+            let (x2, y2) = to_svg(disk.center + disk.velocity*options.arrow_time);
+            let _ = writeln!(out, r#"<line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}" stroke="{}" stroke-width="1" marker-end="url(#arrow)"/>"#, colour(disk.fill));
+        }
+    }
+
+    let _ = writeln!(out, r#"<rect width="{width}" height="{height}" fill="none" stroke="{}" stroke-width="1"/>"#, colour(scene.border));
+    out.push_str("</svg>\n");
+    out
+}
@@ -0,0 +1,344 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::headless::Run;
+use crate::physics::{MaxwellType, Parameters};
+use crate::scenario::Scenario;
+
+/// A parameter of [`Parameters`] a sweep can vary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Swept{
+    Temperature,
+    Radius,
+    FilterHeight,
+    WallWidth,
+    /// Energy a ball moving right needs to pass the temperature demon
+    FilterFast,
+    /// Energy a ball moving left must stay below to pass the temperature demon
+    FilterSlow,
+    /// Constant of the phase-conserving demon
+    FilterC,
+    /// Number of balls of every species
+    Balls
+}
+
+impl Swept{
+    pub const ALL: [Swept; 8] = [
+        Swept::Temperature,
+        Swept::Radius,
+        Swept::FilterHeight,
+        Swept::WallWidth,
+        Swept::FilterFast,
+        Swept::FilterSlow,
+        Swept::FilterC,
+        Swept::Balls
+    ];
+
+    /// Name on the command line and in the CSV header
+    pub fn name(self) -> &'static str{
+        match self{
+            Swept::Temperature => "temperature",
+            Swept::Radius => "radius",
+            Swept::FilterHeight => "filter_height",
+            Swept::WallWidth => "wall_width",
+            Swept::FilterFast => "fast",
+            Swept::FilterSlow => "slow",
+            Swept::FilterC => "c",
+            Swept::Balls => "balls",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self>{
+        Self::ALL.into_iter().find(|s| s.name() == name)
+    }
+
+    /// Sets the parameter to `value`, failing if the demon has no such parameter
+    pub fn set(self, parameters: &mut Parameters, value: f64) -> Result<(), String>{
+        match (self, &mut parameters.filter_type){
+            (Swept::Temperature, _) => parameters.temperature = value as f32,
+            (Swept::Radius, _) => parameters.radius = value as f32,
+            (Swept::FilterHeight, _) => parameters.geometry.filter_height = value as f32,
+            (Swept::WallWidth, _) => parameters.geometry.wall_width = value as f32,
+            (Swept::FilterFast, MaxwellType::Temperature{fast, ..}) => *fast = value as f32,
+            (Swept::FilterSlow, MaxwellType::Temperature{slow, ..}) => *slow = value as f32,
+            (Swept::FilterC, MaxwellType::PhaseConserving{c}) => *c = value as f32,
+            (Swept::Balls, _) => {
+                // the cast would clamp, leaving the axis value different from the count that ran
+                if !value.is_finite() || !(0.0..=u16::MAX as f64).contains(&value.round()){
+                    return Err(format!("balls must be a count from 0 to {}, got {value}", u16::MAX))
+                }
+                for species in &mut parameters.species{
+                    species.count = value.round() as u16;
+                }
+            },
+            (swept, filter_type) => return Err(format!("{filter_type:?} demon has no parameter {}", swept.name()))
+        }
+        Ok(())
+    }
+}
+
+/// Values one parameter takes in a sweep
+#[derive(Debug, Clone, PartialEq)]
+pub struct Axis{
+    pub parameter: Swept,
+    pub values: Vec<f64>
+}
+
+impl Axis{
+    /// Parses `name=from:to:count` (evenly spaced, ends included) or `name=v1,v2,...`
+    pub fn parse(text: &str) -> Result<Self, String>{
+        let (name, range) = text.split_once('=').ok_or(format!("Expected name=from:to:count, got {text}"))?;
+        let parameter = Swept::from_name(name).ok_or_else(|| {
+            let names: Vec<_> = Swept::ALL.iter().map(|s| s.name()).collect();
+            format!("Unknown parameter {name}, expected one of {}", names.join(", "))
+        })?;
+        let number = |s: &str| s.trim().parse::<f64>().map_err(|_| format!("Invalid number in {text}: {s}"));
+        let values = if let [from, to, count] = range.split(':').collect::<Vec<_>>()[..]{
+            let (from, to) = (number(from)?, number(to)?);
+            let count: usize = count.trim().parse().map_err(|_| format!("Invalid count in {text}: {count}"))?;
+            match count{
+                0 => vec![],
+                1 => vec![from],
+                _ => (0..count).map(|i| from + (to - from)*i as f64/(count - 1) as f64).collect()
+            }
+        }
+        else{
+            range.split(',').map(number).collect::<Result<_, _>>()?
+        };
+        if values.is_empty(){
+            return Err(format!("No values in {text}"))
+        }
+        Ok(Self{parameter, values})
+    }
+}
+
+/// Mean and standard error of an observable over the seeds of a grid point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate{
+    pub mean: f64,
+    /// `NaN` for a single seed
+    pub stderr: f64
+}
+
+impl Estimate{
+    pub fn from_values(values: &[f64]) -> Self{
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>()/n;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>()/(n - 1.0);
+        Self{mean, stderr: (variance/n).sqrt()}
+    }
+}
+
+/// Averages of a single run
+#[derive(Debug, Clone, PartialEq)]
+struct Measurement{
+    /// Time average of every observable, `None` if it was never defined
+    means: Vec<Option<f64>>,
+    /// When every observable settled, `None` if it didn't
+    relaxation_times: Vec<Option<f64>>
+}
+
+impl Measurement{
+    /// Whether every observable that was ever defined settled
+    fn settled(&self) -> bool{
+        self.means.iter().zip(&self.relaxation_times).all(|(mean, relaxation)| mean.is_none() || relaxation.is_some())
+    }
+}
+
+/// Results at one point of the grid
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point{
+    /// Value of every axis, in the order of [`Sweep::axes`]
+    pub values: Vec<f64>,
+    /// One estimate per observable of the scenario, `None` if it wasn't defined in any run
+    pub estimates: Vec<Option<Estimate>>,
+    /// Mean relaxation time of every observable over the runs it settled in, `None` if it settled in none
+    pub relaxation_times: Vec<Option<f64>>,
+    /// Runs in which some observable didn't settle even at [`Sweep::max_duration`],
+    /// averaged over their second half instead
+    pub unsettled: u32
+}
+
+/// How many times the scenario duration a run may be extended to by default
+pub const DEFAULT_EXTENSION: f64 = 10.0;
+
+/// Runs a scenario over a grid of one or two parameters, several seeds per grid point
+#[derive(Debug, Clone)]
+pub struct Sweep{
+    pub scenario: Scenario,
+    pub axes: Vec<Axis>,
+    /// Runs per grid point, seeded with consecutive seeds from the scenario one
+    pub seeds: u32,
+    /// Simulated time discarded at the start of every run, before averaging,
+    /// `None` to detect the [`crate::steady::SteadyState`] of every observable
+    pub equilibration: Option<f64>,
+    /// Simulated time a run is extended to at most while its observables haven't settled,
+    /// [`DEFAULT_EXTENSION`] times the scenario duration by default
+    pub max_duration: Option<f64>,
+    /// Worker threads, all cores by default
+    pub threads: usize
+}
+
+impl Sweep{
+    pub fn new(scenario: Scenario, axes: Vec<Axis>) -> Self{
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Self{scenario, axes, seeds: 4, equilibration: None, max_duration: None, threads}
+    }
+
+    /// Longest simulated time of a run
+    pub fn longest(&self) -> f64{
+        self.max_duration.unwrap_or(DEFAULT_EXTENSION*self.scenario.duration)
+    }
+
+    /// Every combination of the axes values, the first axis varying slowest
+    pub fn grid(&self) -> Vec<Vec<f64>>{
+        self.axes.iter().fold(vec![vec![]], |points, axis| {
+            points.iter()
+                .flat_map(|point| axis.values.iter().map(move |&v| [&point[..], &[v]].concat()))
+                .collect()
+        })
+    }
+
+    /// Checks that every grid point makes a valid scenario
+    pub fn validate(&self) -> Result<(), String>{
+        if !(1..=2).contains(&self.axes.len()){
+            return Err("A sweep varies one or two parameters".into())
+        }
+        if self.seeds == 0{
+            return Err("A sweep needs at least one seed".into())
+        }
+        if let Some(e) = self.equilibration && (e.is_nan() || e < 0.0 || e >= self.scenario.duration){
+            return Err(format!("equilibration must be between 0 and the duration {}, got {e}", self.scenario.duration))
+        }
+        if let Some(longest) = self.max_duration && (longest.is_nan() || longest < self.scenario.duration){
+            return Err(format!("max duration can't be shorter than the duration {}, got {longest}", self.scenario.duration))
+        }
+        for point in self.grid(){
+            self.scenario_at(&point, 0)?.validate()?;
+        }
+        Ok(())
+    }
+
+    fn scenario_at(&self, point: &[f64], seed: u32) -> Result<Scenario, String>{
+        let mut scenario = self.scenario.clone();
+        for (axis, &value) in self.axes.iter().zip(point){
+            axis.parameter.set(&mut scenario.parameters, value)?;
+        }
+        scenario.parameters.seed = scenario.parameters.seed.wrapping_add(seed as u64);
+        Ok(scenario)
+    }
+
+    /// Time average of every observable after the equilibration, and when each of them settled.
+    /// Without a fixed equilibration the run goes on, a scenario duration at a time,
+    /// until every observable settles or [`Sweep::longest`] is reached
+    fn measure(&self, scenario: &Scenario) -> Result<Measurement, String>{
+        let mut run = Run::from_scenario(scenario)?;
+        run.run_for(scenario.duration);
+        let relaxation_times = |run: &Run| scenario.observables.iter()
+            .map(|&o| run.recorder.steady_state(o).map(|s| s.relaxation_time))
+            .collect::<Vec<_>>();
+        let mean_after = |run: &Run, o, time| {
+            let values: Vec<f64> = run.recorder.samples().iter().filter(|s| s.time > time).filter_map(|s| s.get(o)).collect();
+            if values.is_empty(){None}else{Some(values.iter().sum::<f64>()/values.len() as f64)}
+        };
+        let means = |run: &Run, time| scenario.observables.iter().map(|&o| mean_after(run, o, time)).collect();
+
+        if let Some(time) = self.equilibration{
+            return Ok(Measurement{means: means(&run, time), relaxation_times: relaxation_times(&run)})
+        }
+        let longest = self.longest();
+        loop{
+            let measurement = Measurement{means: means(&run, 0.0), relaxation_times: relaxation_times(&run)};
+            // half a step of slack, like `Run::run_for`
+            let left = longest - run.simulation.time - run.dt as f64*0.5;
+            if measurement.settled() || left <= 0.0{
+                break
+            }
+            run.run_for(scenario.duration.min(left));
+        }
+        let time = run.simulation.time;
+        let means = scenario.observables.iter().map(|&o| match run.recorder.steady_state(o){
+            Some(steady) => Some(steady.mean),
+            None => mean_after(&run, o, time/2.0)
+        }).collect();
+        Ok(Measurement{means, relaxation_times: relaxation_times(&run)})
+    }
+
+    /// Runs every grid point and seed, spreading the runs over [`Sweep::threads`] threads
+    pub fn run(&self) -> Result<Vec<Point>, String>{
+        self.validate()?;
+        let grid = self.grid();
+        let jobs: Vec<_> = grid.iter()
+            .flat_map(|point| (0..self.seeds).map(move |seed| (point, seed)))
+            .collect();
+        let next = AtomicUsize::new(0);
+        let mut results = vec![Ok(Measurement{means: vec![], relaxation_times: vec![]}); jobs.len()];
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.threads.clamp(1, jobs.len())).map(|_| scope.spawn(|| {
+                let mut done = vec![];
+                loop{
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(&(point, seed)) = jobs.get(i) else{
+                        return done
+                    };
+                    let scenario = self.scenario_at(point, seed).expect("Grid points were validated");
+                    done.push((i, self.measure(&scenario)));
+                }
+            })).collect();
+            for worker in workers{
+                for (i, measured) in worker.join().expect("Runs don't panic"){
+                    results[i] = measured;
+                }
+            }
+        });
+        let results = results.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+        let observables = self.scenario.observables.len();
+        Ok(grid.into_iter().zip(results.chunks(self.seeds as usize)).map(|(values, runs)| {
+            let estimates = (0..observables).map(|o| {
+                let values: Vec<_> = runs.iter().filter_map(|run| run.means[o]).collect();
+                if values.is_empty(){None}else{Some(Estimate::from_values(&values))}
+            }).collect();
+            let relaxation_times = (0..observables).map(|o| {
+                let times: Vec<_> = runs.iter().filter_map(|run| run.relaxation_times[o]).collect();
+                if times.is_empty(){None}else{Some(times.iter().sum::<f64>()/times.len() as f64)}
+            }).collect();
+            let unsettled = runs.iter().filter(|run| !run.settled()).count() as u32;
+            Point{values, estimates, relaxation_times, unsettled}
+        }).collect())
+    }
+
+    /// Results as CSV, a row per grid point with the mean, the standard error and the relaxation time
+    /// of every observable. Cells of observables that weren't defined or didn't settle are left empty
+    pub fn to_csv(&self, points: &[Point]) -> String{
+        let mut out = String::new();
+        let _ = writeln!(out, "# parameters = {}", serde_json::to_string(&self.scenario.parameters).unwrap_or_default());
+        let _ = writeln!(out, "# dt = {}", self.scenario.dt);
+        let _ = writeln!(out, "# window = {}", self.scenario.window);
+        let _ = writeln!(out, "# duration = {}", self.scenario.duration);
+        if self.equilibration.is_none(){
+            let _ = writeln!(out, "# max_duration = {}", self.longest());
+        }
+        let _ = match self.equilibration{
+            Some(time) => writeln!(out, "# equilibration = {time}"),
+            None => writeln!(out, "# equilibration = detected")
+        };
+        let _ = writeln!(out, "# seeds = {}", self.seeds);
+        let header: Vec<_> = self.axes.iter().map(|a| a.parameter.name().to_string())
+            .chain(self.scenario.observables.iter().flat_map(|o| ["mean", "stderr", "relaxation_time"].map(|c| format!("{}_{c}", o.name()))))
+            .chain(["unsettled".to_string()])
+            .collect();
+        let _ = writeln!(out, "{}", header.join(","));
+        for point in points{
+            let row: Vec<_> = point.values.iter().map(f64::to_string)
+                .chain(point.estimates.iter().zip(&point.relaxation_times).flat_map(|(estimate, relaxation)| {
+                    let cell = |value: Option<f64>| value.map_or(String::new(), |v| v.to_string());
+                    [cell(estimate.map(|e| e.mean)), cell(estimate.map(|e| e.stderr)), cell(*relaxation)]
+                }))
+                .chain([point.unsettled.to_string()])
+                .collect();
+            let _ = writeln!(out, "{}", row.join(","));
+        }
+        out
+    }
+}
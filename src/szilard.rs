@@ -0,0 +1,138 @@
+use std::f64::consts::LN_2;
+
+use crate::scene::{Segment, Style};
+use crate::vector::{Real, Vector, to_f64};
+
+/// Pace of the [`Szilard`] engine cycle
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct SzilardParameters{
+    /// Speed the piston moves out with, slow against the ball so the expansion is almost reversible
+    pub piston_speed: f32,
+    /// Time without a partition between two cycles, for the ball to forget its side
+    pub idle: f32
+}
+
+impl Default for SzilardParameters{
+    fn default() -> Self{
+        Self{piston_speed: 0.05, idle: 1.0}
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum Stage{
+    /// No partition until the time the next one is inserted
+    Idle{until: f64},
+    /// The partition is inserted and the side of the ball measured, it now moves away from the ball as a piston
+    Expanding{left: bool}
+}
+
+/// Szilard's single-molecule engine, in a box held at a temperature by [`crate::physics::ThermalWalls`].
+///
+/// Every cycle a partition is inserted in the middle of the box, the side of the ball is measured
+/// and written into a one-bit memory, and the partition is pushed out as a piston by the ball.
+/// Erasing the memory for the next cycle costs at least kT ln 2, which is all the work the expansion gives.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+pub struct Szilard{
+    pub parameters: SzilardParameters,
+    pub stage: Stage,
+    /// Position of the partition while it's inserted
+    pub piston: Real,
+    /// Temperature the memory is erased at, in the units of [`crate::physics::Parameters::temperature`]
+    pub bath: Real,
+    /// Finished cycles
+    pub cycles: u64,
+    /// Bits measured, one per cycle
+    pub bits: u64,
+    /// The last measured side, `true` for the left one, until it's erased
+    pub memory: Option<bool>,
+    /// Work the ball did on the piston
+    pub work: f64,
+    /// Landauer cost of the erased bits
+    pub erasure: f64
+}
+
+impl Szilard{
+    pub fn new(parameters: SzilardParameters, bath: Real) -> Self{
+        Self{
+            parameters,
+            stage: Stage::Idle{until: parameters.idle as f64},
+            piston: 0.0,
+            bath,
+            cycles: 0,
+            bits: 0,
+            memory: None,
+            work: 0.0,
+            erasure: 0.0
+        }
+    }
+
+    /// kT ln 2 of the bath. Temperatures here are mass times squared speed, that is 2kT in two dimensions
+    pub fn bit_energy(&self) -> f64{
+        to_f64(self.bath)/2.0*LN_2
+    }
+
+    /// Work extracted minus the erasure cost, never positive on average
+    pub fn net_work(&self) -> f64{
+        self.work - self.erasure
+    }
+
+    /// Moves the cycle on by `t` at `time`, measuring the ball at `measured` when inserting the partition
+    pub fn advance(&mut self, time: f64, t: Real, measured: Vector, radius: Real, width: Real){
+        match self.stage{
+            Stage::Idle{until} => {
+                let middle = width/2.0;
+                // the partition can't cut the ball, so it waits for the ball to move off the middle
+                if time >= until && (measured.x - middle).abs() > radius{
+                    let left = measured.x < middle;
+                    self.piston = middle;
+                    self.memory = Some(left);
+                    self.bits += 1;
+                    self.stage = Stage::Expanding{left};
+                }
+            },
+            Stage::Expanding{left} => {
+                let step = self.parameters.piston_speed as Real*t;
+                self.piston += if left{step}else{-step};
+                if self.piston <= 0.0 || self.piston >= width{
+                    self.cycles += 1;
+                    self.memory = None;
+                    self.erasure += self.bit_energy();
+                    self.stage = Stage::Idle{until: time + self.parameters.idle as f64};
+                }
+            }
+        }
+    }
+
+    /// Bounces a ball of `mass` off the moving piston, keeping it on its side
+    pub fn collide(&mut self, coord: &mut Vector, speed: &mut Vector, mass: Real, radius: Real){
+        let Stage::Expanding{left} = self.stage else{
+            return
+        };
+        // in the frame of the piston, with the x axis pointing at it
+        let sign = if left{1.0}else{-1.0};
+        let u = self.parameters.piston_speed as Real;
+        if sign*(coord.x - self.piston) <= -radius{
+            return
+        }
+        coord.x = self.piston - sign*radius;
+        if sign*speed.x > u{
+            let before = speed.length_sq();
+            speed.x = sign*(2.0*u) - speed.x;
+            self.work += to_f64(mass*(before - speed.length_sq()))/2.0;
+        }
+    }
+
+    /// The inserted partition, for the [`crate::scene::Scene`]
+    pub fn segments(&self, height: Real, style: Style) -> Vec<Segment>{
+        match self.stage{
+            Stage::Idle{..} => vec![],
+            Stage::Expanding{..} => vec![Segment{
+                from: Vector::new(self.piston, 0.0).to_pos2(),
+                to: Vector::new(self.piston, height).to_pos2(),
+                width: 0.006,
+                colour: style.gray(160)
+            }]
+        }
+    }
+}
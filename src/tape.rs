@@ -0,0 +1,77 @@
+/// Finite memory of the [`crate::physics::MaxwellType::Informed`] demon.
+///
+/// The demon writes a bit for every ball it measures. Once the tape is full it's erased at once,
+/// paying [`Tape::erasure`] a bit either to a heat bath or out of the kinetic energy of the gas.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+pub struct Tape{
+    pub capacity: u32,
+    /// Energy an erased bit costs
+    pub erasure: f64,
+    /// Whether the erasure is paid by the gas rather than by a heat bath
+    pub from_gas: bool,
+    /// kT the stored free energy is measured at
+    pub kt: f64,
+    /// Bits written since the last erasure, `true` for a ball let through
+    pub bits: Vec<bool>,
+    /// Bits written since the initiation
+    pub written: u64,
+    pub erased: u64,
+    /// Erasure energy paid by the heat bath
+    pub paid_by_bath: f64,
+    /// Erasure energy taken from the gas
+    pub paid_by_gas: f64
+}
+
+impl Tape{
+    pub fn new(capacity: u32, erasure: f64, from_gas: bool, kt: f64) -> Self{
+        Self{capacity, erasure, from_gas, kt, bits: vec![], written: 0, erased: 0, paid_by_bath: 0.0, paid_by_gas: 0.0}
+    }
+
+    /// Writes a measurement, returning the energy the gas owes if the tape was full and had to be erased
+    pub fn write(&mut self, bit: bool) -> Option<f64>{
+        let mut owed = None;
+        if self.bits.len() >= self.capacity.max(1) as usize{
+            let cost = self.bits.len() as f64*self.erasure;
+            self.erased += self.bits.len() as u64;
+            self.bits.clear();
+            if self.from_gas{
+                owed = Some(cost);
+            }
+            else{
+                self.paid_by_bath += cost;
+            }
+        }
+        self.bits.push(bit);
+        self.written += 1;
+        owed
+    }
+
+    /// Energy paid for the erasures so far
+    pub fn paid(&self) -> f64{
+        self.paid_by_bath + self.paid_by_gas
+    }
+
+    /// Free energy stored by sorting the balls into `left` and `right` chambers of the same size,
+    /// the work an isothermal expansion back to equal densities would give
+    pub fn stored_free_energy(&self, left: usize, right: usize) -> f64{
+        sorting_free_energy(self.kt, left, right)
+    }
+
+    /// Cost of erasing the bits still on the tape
+    pub fn pending(&self) -> f64{
+        self.bits.len() as f64*self.erasure
+    }
+
+    /// Stored free energy minus the erasure cost, counting the bits still on the tape as erased.
+    /// Negative when the erasures paid for the sorting
+    pub fn balance(&self, left: usize, right: usize) -> f64{
+        self.stored_free_energy(left, right) - self.paid() - self.pending()
+    }
+}
+
+/// Free energy at `kt` stored by sorting the balls into `left` and `right` chambers of the same size
+pub fn sorting_free_energy(kt: f64, left: usize, right: usize) -> f64{
+    let n = (left + right) as f64;
+    let term = |k: usize| if k == 0{0.0}else{k as f64*(2.0*k as f64/n).ln()};
+    kt*(term(left) + term(right))
+}
@@ -0,0 +1,140 @@
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+/// Scalar of the physics, `f64` with the `f64` feature for long or reversibility-sensitive runs
+#[cfg(not(feature = "f64"))]
+pub type Real = f32;
+/// Scalar of the physics, `f64` with the `f64` feature for long or reversibility-sensitive runs
+#[cfg(feature = "f64")]
+pub type Real = f64;
+
+#[cfg(not(feature = "f64"))]
+pub use std::f32::consts::PI;
+#[cfg(feature = "f64")]
+pub use std::f64::consts::PI;
+
+/// Single precision copy of a scalar, for painting
+#[allow(clippy::unnecessary_cast)] // no-op without the `f64` feature
+pub fn to_f32(x: Real) -> f32{
+    x as f32
+}
+
+/// Double precision copy of a scalar, for statistics
+#[allow(clippy::unnecessary_cast)] // no-op with the `f64` feature
+pub fn to_f64(x: Real) -> f64{
+    x as f64
+}
+
+/// Scalar of the physics from a double precision one
+#[allow(clippy::unnecessary_cast)] // no-op with the `f64` feature
+pub fn from_f64(x: f64) -> Real{
+    x as Real
+}
+
+/// Two-dimensional vector of the physics.
+///
+/// Serialized as `{x, y}`, like [`egui::Vec2`], so snapshots of either precision read each other.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vector{
+    pub x: Real,
+    pub y: Real
+}
+
+impl Vector{
+    pub const ZERO: Self = Self{x: 0.0, y: 0.0};
+
+    pub const fn new(x: Real, y: Real) -> Self{
+        Self{x, y}
+    }
+
+    /// Vector of the given length pointing at `angle` radians from the x axis
+    pub fn from_polar(length: Real, angle: Real) -> Self{
+        Self::new(length*angle.cos(), length*angle.sin())
+    }
+
+    pub fn length(self) -> Real{
+        self.length_sq().sqrt()
+    }
+
+    pub fn length_sq(self) -> Real{
+        self.x*self.x + self.y*self.y
+    }
+
+    /// Angle from the x axis, in `-PI..=PI`
+    pub fn angle(self) -> Real{
+        self.y.atan2(self.x)
+    }
+
+    pub fn dot(self, other: Self) -> Real{
+        self.x*other.x + self.y*other.y
+    }
+
+    /// Rotated counterclockwise by `angle` radians
+    pub fn rotated(self, angle: Real) -> Self{
+        let (sin, cos) = angle.sin_cos();
+        Self::new(cos*self.x - sin*self.y, sin*self.x + cos*self.y)
+    }
+
+    /// Single precision copy, for painting
+    pub fn to_vec2(self) -> egui::Vec2{
+        egui::Vec2::new(to_f32(self.x), to_f32(self.y))
+    }
+
+    /// Single precision copy, for painting
+    pub fn to_pos2(self) -> egui::Pos2{
+        egui::Pos2::new(to_f32(self.x), to_f32(self.y))
+    }
+}
+
+impl Add for Vector{
+    type Output = Self;
+    fn add(self, other: Self) -> Self{
+        Self::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl AddAssign for Vector{
+    fn add_assign(&mut self, other: Self){
+        *self = *self + other;
+    }
+}
+
+impl Sub for Vector{
+    type Output = Self;
+    fn sub(self, other: Self) -> Self{
+        Self::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl SubAssign for Vector{
+    fn sub_assign(&mut self, other: Self){
+        *self = *self - other;
+    }
+}
+
+impl Neg for Vector{
+    type Output = Self;
+    fn neg(self) -> Self{
+        Self::new(-self.x, -self.y)
+    }
+}
+
+impl Mul<Real> for Vector{
+    type Output = Self;
+    fn mul(self, factor: Real) -> Self{
+        Self::new(self.x*factor, self.y*factor)
+    }
+}
+
+impl Mul<Vector> for Real{
+    type Output = Vector;
+    fn mul(self, vector: Vector) -> Vector{
+        vector*self
+    }
+}
+
+impl Div<Real> for Vector{
+    type Output = Self;
+    fn div(self, divisor: Real) -> Self{
+        Self::new(self.x/divisor, self.y/divisor)
+    }
+}